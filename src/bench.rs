@@ -0,0 +1,238 @@
+extern crate test;
+
+use test::Bencher;
+
+use crate::config::Config;
+use crate::frecency::Frecency;
+use crate::frecent_paths::PathFrecency;
+
+// Both pazi's matching and the baseline it's compared against score this many paths
+const BENCH_SIZE: usize = 10_000;
+
+const WORDS: &[&str] = &[
+    "src",
+    "projects",
+    "work",
+    "kubernetes",
+    "pazi",
+    "docs",
+    "build",
+    "target",
+    "release",
+    "api",
+    "frontend",
+    "backend",
+    "scripts",
+    "Downloads",
+    "notes",
+    "go",
+    "rust",
+    "café",
+    "vendor",
+    "tmp",
+];
+
+// bench_paths returns 'n' distinct paths made up of a handful of plausible components
+fn bench_paths(n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| {
+            let mut path = String::from("/home/user");
+            // walk the digits of i to pick between 2 and 6 components
+            let mut rest = i;
+            for _ in 0..2 + i % 5 {
+                path.push('/');
+                path.push_str(WORDS[rest % WORDS.len()]);
+                rest = rest / WORDS.len() + i;
+            }
+            format!("{}-{}", path, i)
+        })
+        .collect()
+}
+
+fn large_db(paths: &[String]) -> (tempfile::TempDir, PathFrecency) {
+    let dir = tempfile::tempdir().unwrap();
+    let mut f = PathFrecency::load(&dir.path().join("db")).unwrap();
+    // the default size would trim the database well below the number of paths
+    f.set_limits(paths.len(), Config::default().database.half_life());
+    for path in paths {
+        f.visit(path.clone());
+    }
+    (dir, f)
+}
+
+// query_bench ranks every path in a large database, including building its match index, as a
+// single pazi invocation would.
+fn query_bench(b: &mut Bencher, query: &str) {
+    let paths = bench_paths(BENCH_SIZE);
+    let (_dir, db) = large_db(&paths);
+    assert_eq!(db.paths().len(), BENCH_SIZE);
+    b.iter(|| {
+        let mut f = db.clone();
+        let _ = f.directory_matches(query);
+    });
+}
+
+// baseline_query_bench ranks the same paths the way pazi 0.5.0 did, before any of its newer
+// matchers existed, as the point of comparison for the benchmarks above.
+fn baseline_query_bench(b: &mut Bencher, query: &str) {
+    let paths = bench_paths(BENCH_SIZE);
+    let mut frecency = Frecency::with_half_life(BENCH_SIZE, Config::default().database.half_life());
+    for path in &paths {
+        frecency.visit(path.clone());
+    }
+    b.iter(|| {
+        let f = frecency.clone();
+        let _ = baseline::directory_matches(&f, query);
+    });
+}
+
+#[bench]
+fn bench_query_10k(b: &mut Bencher) {
+    query_bench(b, "kube api");
+}
+
+#[bench]
+fn bench_baseline_query_10k(b: &mut Bencher) {
+    baseline_query_bench(b, "kube api");
+}
+
+#[bench]
+fn bench_query_10k_no_match(b: &mut Bencher) {
+    query_bench(b, "xyzzy");
+}
+
+#[bench]
+fn bench_baseline_query_10k_no_match(b: &mut Bencher) {
+    baseline_query_bench(b, "xyzzy");
+}
+
+// baseline is pazi 0.5.0's matching, copied as it was
+mod baseline {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use crate::frecency::{descending_frecency, Frecency};
+
+    trait Matcher {
+        fn matches(&self, input: &str, search: &str) -> Option<f64>;
+    }
+
+    struct ExactMatcher {}
+    impl Matcher for ExactMatcher {
+        fn matches(&self, input: &str, search: &str) -> Option<f64> {
+            if input == search {
+                return Some(1.0);
+            }
+            None
+        }
+    }
+
+    struct SubstringMatcher {}
+    impl Matcher for SubstringMatcher {
+        fn matches(&self, input: &str, search: &str) -> Option<f64> {
+            let res = input.find(search);
+            match res {
+                None => None,
+                Some(offset) => {
+                    let base = if offset == 0 { 1.0 } else { 0.8 };
+                    Some(base * search.len() as f64 / input.len() as f64)
+                }
+            }
+        }
+    }
+
+    struct PathComponentMatcher<'a>(&'a dyn Matcher);
+
+    impl<'a> Matcher for PathComponentMatcher<'a> {
+        fn matches(&self, input: &str, search: &str) -> Option<f64> {
+            let p = Path::new(input);
+            let components = p.components();
+            let num_components = p.components().count();
+            let mut weight = 0.9;
+            let weight_step = (weight - 0.2) / num_components as f64;
+            let mut res = None;
+            for component in components.rev() {
+                let s = match component.as_os_str().to_str() {
+                    Some(s) => s,
+                    None => {
+                        continue;
+                    }
+                };
+                if let Some(v) = self.0.matches(s, search) {
+                    let attv = v * weight;
+                    res = match res {
+                        None => Some(attv),
+                        Some(existing) => {
+                            if attv > existing {
+                                Some(attv)
+                            } else {
+                                Some(existing)
+                            }
+                        }
+                    }
+                }
+                weight -= weight_step;
+            }
+            res
+        }
+    }
+
+    struct CaseInsensitiveMatcher<'a>(&'a dyn Matcher);
+
+    impl<'a> Matcher for CaseInsensitiveMatcher<'a> {
+        fn matches(&self, input: &str, search: &str) -> Option<f64> {
+            self.0
+                .matches(&input.to_lowercase(), &search.to_lowercase())
+                .map(|f| f * 0.7)
+        }
+    }
+
+    pub(super) fn directory_matches(
+        frecency: &Frecency<String>,
+        filter: &str,
+    ) -> Vec<(String, f64)> {
+        let em = ExactMatcher {};
+        let sm = SubstringMatcher {};
+        let ci_em = CaseInsensitiveMatcher(&em);
+        let pc_em = PathComponentMatcher(&em);
+        let pc_sm = PathComponentMatcher(&sm);
+        let pc_ci_em = PathComponentMatcher(&ci_em);
+        let ci_sm = CaseInsensitiveMatcher(&sm);
+        let pc_ci_sm = PathComponentMatcher(&ci_sm);
+        let matchers: Vec<&dyn Matcher> = vec![
+            &ExactMatcher {},
+            &ci_em,
+            &pc_em,
+            &pc_sm,
+            &pc_ci_em,
+            &SubstringMatcher {},
+            &ci_sm,
+            &pc_ci_sm,
+        ];
+
+        let mut dedupe_map: HashMap<String, f64> = HashMap::new();
+        let items = frecency.items().normalized();
+        let matched = items.iter().flat_map(|item| {
+            matchers.iter().filter_map(move |m| {
+                m.matches(item.0, filter)
+                    .map(move |v| (item.0, v * 0.8 + item.1 * 0.2))
+            })
+        });
+        for el in matched {
+            match dedupe_map.get_mut(el.0) {
+                Some(val) => {
+                    if el.1 > *val {
+                        *val = el.1
+                    }
+                }
+                None => {
+                    dedupe_map.insert(el.0.to_owned(), el.1);
+                }
+            };
+        }
+
+        let mut deduped: Vec<_> = dedupe_map.into_iter().collect();
+        deduped.sort_by(descending_frecency);
+        deduped
+    }
+}
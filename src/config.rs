@@ -0,0 +1,508 @@
+// config holds pazi's settings. They're read from 'config.toml' in pazi's config directory, and
+// each of them may be overridden by an environment variable named after its key, e.g.
+// 'database.max_size' by PAZI_DATABASE_MAX_SIZE. Everything is optional; anything left out keeps
+// its default.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use super::matcher::MatchOptions;
+
+pub const CONFIG_NAME: &str = "config.toml";
+const DB_NAME: &str = "pazi_dirs.msgpack";
+// a hundred years is as good as forever
+const MAX_HALF_LIFE_DAYS: f64 = 36500.0;
+
+// KEYS lists every setting by its dotted path in the config file.
+pub const KEYS: &[&str] = &[
+    "database.path",
+    "database.max_size",
+    "database.half_life_days",
+    "exclusions",
+    "jump.pipe",
+    "matching.smart_case",
+    "matching.weights.match_ratio",
+    "matching.weights.case_insensitive",
+    "matching.weights.non_prefix_substring",
+    "matching.weights.punctuation_insensitive",
+    "matching.weights.diacritic_insensitive",
+    "matching.weights.component_falloff_start",
+    "matching.weights.component_falloff_end",
+];
+
+// LEGACY_ENV maps environment variables which predate the config file to the key they override
+pub const LEGACY_ENV: &[(&str, &str)] = &[("PAZI_SMART_CASE", "matching.smart_case")];
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    // exclusions are globs of paths which are never recorded, in addition to those in the ignore
+    // file
+    pub exclusions: Vec<String>,
+    pub jump: JumpConfig,
+    pub matching: MatchOptions,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    // path is where the database is stored; by default, alongside the config file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    // max_size is how many directories, and separately how many files, are remembered
+    pub max_size: usize,
+    // half_life_days is how long it takes for a visit to count for half as much
+    pub half_life_days: u64,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            path: None,
+            max_size: 1000,
+            half_life_days: 30,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn half_life(&self) -> Duration {
+        Duration::from_secs(self.half_life_days * 24 * 60 * 60)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct JumpConfig {
+    // pipe is a program, such as 'fzf', to pick between matches with when jumping interactively,
+    // rather than pazi's own interactive filter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipe: Option<String>,
+}
+
+impl Config {
+    // load reads the config file in the given config directory, and applies any overrides from
+    // the environment. A config file which doesn't exist is treated as an empty one.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(CONFIG_NAME);
+        let mut table = read_table(&path)?;
+        apply_env(&mut table, |name| env::var(name).ok())?;
+        let mut config: Config = Value::Table(table)
+            .try_into()
+            .with_context(|| format!("invalid config file: {:?}", path))?;
+        config
+            .validate()
+            .with_context(|| format!("invalid config file: {:?}", path))?;
+        // relative paths are relative to the config directory, not wherever pazi happens to run
+        config.database.path = Some(match config.database.path.take() {
+            Some(path) => config_dir.join(expand_home(path)),
+            None => config_dir.join(DB_NAME),
+        });
+        Ok(config)
+    }
+
+    // validate checks every setting is within its allowed range. Values which deserialize fine
+    // may still be nonsensical, e.g. a half-life of 0 would decay every visit to nothing at once.
+    fn validate(&self) -> Result<()> {
+        let value = Value::try_from(self)?;
+        for key in KEYS {
+            if let Some(v) = lookup(&value, key) {
+                check_value(key, v)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn db_path(&self) -> &Path {
+        self.database
+            .path
+            .as_deref()
+            .expect("the database path is set when loading")
+    }
+
+    // get returns the value of a setting, or None if it isn't set
+    pub fn get(&self, key: &str) -> Result<Option<Value>> {
+        check_key(key)?;
+        let value = Value::try_from(self)?;
+        Ok(lookup(&value, key).cloned())
+    }
+}
+
+// env_var returns the name of the environment variable which overrides a key
+pub fn env_var(key: &str) -> String {
+    format!("PAZI_{}", key.replace('.', "_").to_uppercase())
+}
+
+// set sets a key in the config file in the given directory. Settings which take a list take each
+// value given; all others take exactly one. The rest of the file, including comments, is kept as
+// it was.
+pub fn set(config_dir: &Path, key: &str, values: &[&str]) -> Result<()> {
+    let path = config_dir.join(CONFIG_NAME);
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("could not read config file: {:?}", path)),
+    };
+    let mut doc: toml_edit::DocumentMut = data
+        .parse()
+        .with_context(|| format!("invalid config file: {:?}", path))?;
+    let value: toml_edit::Value = parse_value(key, values)?
+        .to_string()
+        .parse()
+        .expect("toml values can be reparsed");
+
+    let (parents, leaf) = match key.rsplit_once('.') {
+        Some((parents, leaf)) => (parents.split('.').collect(), leaf),
+        None => (vec![], key),
+    };
+    let mut table = doc.as_table_mut();
+    for parent in parents {
+        table = table
+            .entry(parent)
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("'{}' in {:?} is not a table", parent, path))?;
+    }
+    table[leaf] = toml_edit::value(value);
+
+    let data = doc.to_string();
+    toml::from_str::<Config>(&data)
+        .map_err(anyhow::Error::from)
+        .and_then(|config| config.validate())
+        .with_context(|| format!("invalid value for '{}'", key))?;
+    fs::write(&path, data).with_context(|| format!("could not write config file: {:?}", path))
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    match fs::read_to_string(path) {
+        Ok(data) => {
+            toml::from_str(&data).with_context(|| format!("invalid config file: {:?}", path))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Table::new()),
+        Err(e) => Err(e).with_context(|| format!("could not read config file: {:?}", path)),
+    }
+}
+
+// apply_env sets each key which has an environment variable set for it. Lists are separated by
+// ':', like PATH.
+fn apply_env(table: &mut Table, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+    let overrides = LEGACY_ENV
+        .iter()
+        .map(|&(name, key)| (name.to_string(), key))
+        .chain(KEYS.iter().map(|&key| (env_var(key), key)));
+    for (name, key) in overrides {
+        let raw = match var(&name) {
+            Some(raw) => raw,
+            None => continue,
+        };
+        // lists are ':' separated, like PATH
+        let values = if is_list(key)? {
+            raw.split(':').collect()
+        } else {
+            vec![raw.as_str()]
+        };
+        let value =
+            parse_value(key, &values).with_context(|| format!("invalid value for {}", name))?;
+        insert(table, key, value);
+    }
+    Ok(())
+}
+
+// expand_home expands a leading '~' in a path to the home directory
+fn expand_home(path: PathBuf) -> PathBuf {
+    let home = directories::UserDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path,
+    }
+}
+
+// default_value returns the default value of a key, or None for settings unset by default.
+fn default_value(key: &str) -> Result<Option<Value>> {
+    check_key(key)?;
+    let default = Value::try_from(Config::default())?;
+    Ok(lookup(&default, key).cloned())
+}
+
+fn is_list(key: &str) -> Result<bool> {
+    Ok(matches!(default_value(key)?, Some(Value::Array(_))))
+}
+
+// parse_value parses values given on the command line or in the environment as the type of the
+// given key.
+fn parse_value(key: &str, values: &[&str]) -> Result<Value> {
+    let default = default_value(key)?;
+    if let Some(Value::Array(_)) = default {
+        return Ok(Value::Array(
+            values
+                .iter()
+                .filter(|v| !v.is_empty())
+                .map(|v| Value::String(v.to_string()))
+                .collect(),
+        ));
+    }
+    let raw = match values {
+        [raw] => *raw,
+        _ => bail!("'{}' takes exactly one value", key),
+    };
+    let invalid = |kind: &str| anyhow!("'{}' takes {}, not '{}'", key, kind, raw);
+    let value = match default {
+        Some(Value::Boolean(_)) => match raw.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Value::Boolean(true),
+            "0" | "false" | "no" | "off" => Value::Boolean(false),
+            _ => return Err(invalid("true or false")),
+        },
+        Some(Value::Integer(_)) => {
+            Value::Integer(raw.parse().map_err(|_| invalid("a whole number"))?)
+        }
+        Some(Value::Float(_)) => Value::Float(raw.parse().map_err(|_| invalid("a number"))?),
+        // strings, and settings which are unset by default
+        _ => Value::String(raw.to_string()),
+    };
+    check_value(key, &value)?;
+    Ok(value)
+}
+
+// check_value checks a value is in range for its key. Types are left for deserialization to
+// check.
+fn check_value(key: &str, value: &Value) -> Result<()> {
+    let invalid = |requirement: &str| anyhow!("'{}' must be {}, not {}", key, requirement, value);
+    let number = match value {
+        Value::Integer(n) => *n as f64,
+        Value::Float(f) => *f,
+        _ => return Ok(()),
+    };
+    match key {
+        "database.max_size" if number < 1.0 => Err(invalid("at least 1")),
+        // the half-life must also fit in a Duration once it's converted to seconds
+        "database.half_life_days" if !(1.0..=MAX_HALF_LIFE_DAYS).contains(&number) => {
+            Err(invalid(&format!("between 1 and {}", MAX_HALF_LIFE_DAYS)))
+        }
+        // NaN isn't in any range, so it's rejected too
+        _ if key.starts_with("matching.weights.") && !(0.0..=1.0).contains(&number) => {
+            Err(invalid("between 0 and 1"))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_key(key: &str) -> Result<()> {
+    if !KEYS.contains(&key) {
+        bail!("unknown config key '{}'", key);
+    }
+    Ok(())
+}
+
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) {
+    let (parents, leaf) = match key.rsplit_once('.') {
+        Some((parents, leaf)) => (parents.split('.').collect(), leaf),
+        None => (vec![], key),
+    };
+    let mut table = table;
+    for parent in parents {
+        let entry = table
+            .entry(parent)
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            // leave it for deserialization to complain about
+            return;
+        }
+        table = entry.as_table_mut().unwrap();
+    }
+    table.insert(leaf.to_string(), value);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::matcher::MatchWeights;
+
+    fn parse(data: &str, env: &[(&str, &str)]) -> Result<Config> {
+        let mut table: Table = toml::from_str(data)?;
+        apply_env(&mut table, |name| {
+            env.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        })?;
+        Ok(Value::Table(table).try_into()?)
+    }
+
+    #[test]
+    fn parses_partial_config() {
+        let config = parse(
+            r#"
+            exclusions = ["/tmp/*"]
+
+            [matching]
+            smart_case = false
+
+            [matching.weights]
+            match_ratio = 0.5
+            "#,
+            &[],
+        )
+        .unwrap();
+        assert!(!config.matching.smart_case);
+        assert_eq!(config.exclusions, vec!["/tmp/*"]);
+        assert_eq!(
+            config.matching.weights,
+            MatchWeights {
+                match_ratio: 0.5,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(parse("", &[]).unwrap(), Config::default());
+        assert!(parse("[matching.weights]\nmatch_raito = 0.5", &[]).is_err());
+    }
+
+    #[test]
+    fn environment_overrides_config() {
+        let config = parse(
+            "[database]\nmax_size = 10\nhalf_life_days = 7",
+            &[
+                ("PAZI_DATABASE_MAX_SIZE", "20"),
+                ("PAZI_EXCLUSIONS", "/tmp/*:/mnt/*"),
+                ("PAZI_JUMP_PIPE", "fzf --delimiter :"),
+                ("PAZI_SMART_CASE", "off"),
+                ("PAZI_MATCHING_WEIGHTS_MATCH_RATIO", "1"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.database.max_size, 20);
+        assert_eq!(config.database.half_life_days, 7);
+        assert_eq!(config.exclusions, vec!["/tmp/*", "/mnt/*"]);
+        assert_eq!(config.jump.pipe.as_deref(), Some("fzf --delimiter :"));
+        assert!(!config.matching.smart_case);
+        assert_eq!(config.matching.weights.match_ratio, 1.0);
+
+        assert!(parse("", &[("PAZI_DATABASE_MAX_SIZE", "lots")]).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let invalid = [
+            ("database.max_size", "0"),
+            ("database.half_life_days", "0"),
+            ("database.half_life_days", "-1"),
+            ("database.half_life_days", "1000000000000"),
+            ("matching.weights.match_ratio", "-0.1"),
+            ("matching.weights.match_ratio", "1.5"),
+            ("matching.weights.case_insensitive", "2"),
+            ("matching.weights.non_prefix_substring", "nan"),
+            ("matching.weights.component_falloff_end", "inf"),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        for (key, value) in &invalid {
+            // from the environment
+            assert!(parse("", &[(&env_var(key), value)]).is_err(), "{}", key);
+            // from 'pazi config set'
+            assert!(set(dir.path(), key, &[value]).is_err(), "{}", key);
+            // and from the config file itself
+            let (table, leaf) = key.rsplit_once('.').unwrap();
+            let data = format!("[{}]\n{} = {}\n", table, leaf, value);
+            fs::write(dir.path().join(CONFIG_NAME), data).unwrap();
+            assert!(Config::load(dir.path()).is_err(), "{} = {}", key, value);
+            fs::remove_file(dir.path().join(CONFIG_NAME)).unwrap();
+        }
+
+        // the bounds themselves are fine
+        let valid = [
+            ("database.max_size", "1"),
+            ("database.half_life_days", "1"),
+            ("matching.weights.match_ratio", "0"),
+            ("matching.weights.match_ratio", "1"),
+        ];
+        for (key, value) in &valid {
+            assert!(parse("", &[(&env_var(key), value)]).is_ok(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn resolves_relative_database_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(CONFIG_NAME),
+            "[database]\npath = \"db/pazi.msgpack\"\n",
+        )
+        .unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.db_path(), dir.path().join("db/pazi.msgpack"));
+
+        fs::write(
+            dir.path().join(CONFIG_NAME),
+            "[database]\npath = \"/var/pazi.msgpack\"\n",
+        )
+        .unwrap();
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.db_path(), Path::new("/var/pazi.msgpack"));
+    }
+
+    #[test]
+    fn keys_cover_every_setting() {
+        fn leaves(prefix: &str, value: &Value, res: &mut Vec<String>) {
+            match value {
+                Value::Table(table) => {
+                    for (k, v) in table {
+                        let key = if prefix.is_empty() {
+                            k.clone()
+                        } else {
+                            format!("{}.{}", prefix, k)
+                        };
+                        leaves(&key, v, res);
+                    }
+                }
+                _ => res.push(prefix.to_string()),
+            }
+        }
+        let mut config = Config::default();
+        config.database.path = Some(PathBuf::from("/db"));
+        config.jump.pipe = Some("fzf".to_string());
+        let mut keys = Vec::new();
+        leaves("", &Value::try_from(config).unwrap(), &mut keys);
+        keys.sort();
+        let mut expected: Vec<_> = KEYS.iter().map(|k| k.to_string()).collect();
+        expected.sort();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn sets_keys_in_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_NAME);
+        fs::write(&path, "# my settings\n[database]\nmax_size = 10\n").unwrap();
+
+        set(dir.path(), "database.max_size", &["20"]).unwrap();
+        set(dir.path(), "matching.weights.match_ratio", &["0.5"]).unwrap();
+        set(dir.path(), "exclusions", &["/tmp/*", "/mnt/*"]).unwrap();
+        assert!(set(dir.path(), "database.max_size", &["-1"]).is_err());
+        assert!(set(dir.path(), "database.bogus", &["1"]).is_err());
+        assert!(set(dir.path(), "jump.pipe", &["a", "b"]).is_err());
+
+        let data = fs::read_to_string(&path).unwrap();
+        // comments are preserved
+        assert!(data.contains("# my settings\n"));
+        let config = Config::load(dir.path()).unwrap();
+        assert_eq!(config.database.max_size, 20);
+        assert_eq!(config.matching.weights.match_ratio, 0.5);
+        assert_eq!(config.exclusions, vec!["/tmp/*", "/mnt/*"]);
+        assert_eq!(config.db_path(), dir.path().join(DB_NAME));
+    }
+}
@@ -0,0 +1,340 @@
+// doctor diagnoses why pazi might not seem to be learning. That's almost always a problem with the
+// shell's init, such as its hook not being installed or 'pazi' not being on the PATH the hook
+// runs with, rather than with pazi itself.
+
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use super::config::{self, Config};
+use super::frecent_paths::PathKind;
+use super::view::format_age;
+
+// If nothing has been visited for this long, the shell hook probably isn't running
+const RECENT_VISIT: Duration = Duration::from_secs(24 * 60 * 60);
+// Temp files younger than this may belong to a save which is still in progress
+const STALE_TEMP_FILE: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+// Report prints the result of each check as it's made, grouped into sections.
+#[derive(Default)]
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    fn section(&self, name: &str) {
+        println!("{}", name);
+    }
+
+    fn check(&mut self, status: Status, msg: impl Display) {
+        let label = match status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("  [{}] {}", label, msg);
+        self.failed |= status == Status::Fail;
+    }
+
+    // detail prints additional information about the previous check
+    fn detail(&self, msg: impl Display) {
+        println!("         {}", msg);
+    }
+}
+
+// run runs every check, printing the results. It returns false if any check failed; warnings
+// are worth a look, but don't necessarily mean anything is wrong.
+pub fn run(config_dir: &Path, config: &Result<Config>) -> bool {
+    let mut report = Report::default();
+    check_config(&mut report, config_dir, config);
+    let last_visit = match config {
+        Ok(config) => check_database(&mut report, config),
+        Err(_) => None,
+    };
+    check_shell(&mut report, last_visit);
+    if let Ok(config) = config {
+        check_temp_files(&mut report, config.db_path());
+    }
+    !report.failed
+}
+
+fn check_config(report: &mut Report, config_dir: &Path, config: &Result<Config>) {
+    report.section("config");
+    let path = config_dir.join(config::CONFIG_NAME);
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            // toml's errors end with a newline
+            let e = format!("{:#}", e);
+            report.check(Status::Fail, e.trim_end());
+            report.detail("the remaining database checks were skipped");
+            return;
+        }
+    };
+    if path.exists() {
+        report.check(Status::Ok, format!("read {}", path.display()));
+    } else {
+        report.check(
+            Status::Ok,
+            format!("no config file at {}; using defaults", path.display()),
+        );
+    }
+    for key in config::KEYS {
+        if let Ok(Some(value)) = config.get(key) {
+            match overriding_env_var(key) {
+                Some(var) => report.detail(format!("{} = {} (from ${})", key, value, var)),
+                None => report.detail(format!("{} = {}", key, value)),
+            }
+        }
+    }
+}
+
+// overriding_env_var returns the environment variable which is overriding a key, if any
+fn overriding_env_var(key: &str) -> Option<String> {
+    let var = config::env_var(key);
+    if env::var_os(&var).is_some() {
+        return Some(var);
+    }
+    config::LEGACY_ENV
+        .iter()
+        .find(|&&(name, k)| k == key && env::var_os(name).is_some())
+        .map(|(name, _)| name.to_string())
+}
+
+// check_database checks the database can be read and written, returning when the most recent
+// visit was recorded, if it's known.
+fn check_database(report: &mut Report, config: &Config) -> Option<u64> {
+    report.section("database");
+    let path = config.db_path();
+    let existed = path.exists();
+
+    // saving writes a temp file next to the database and renames it over the database
+    let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+    match tempfile::Builder::new()
+        .prefix(".pazi_doctor")
+        .tempfile_in(dir)
+    {
+        Ok(_) => report.check(Status::Ok, format!("{} is writable", dir.display())),
+        Err(e) => report.check(
+            Status::Fail,
+            format!(
+                "can't write to {}, so visits can't be saved: {}",
+                dir.display(),
+                e
+            ),
+        ),
+    }
+
+    if !existed {
+        report.check(
+            Status::Warn,
+            format!(
+                "{} doesn't exist, so nothing has been recorded yet",
+                path.display()
+            ),
+        );
+        return None;
+    }
+    let mut frecency = match crate::load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            report.check(Status::Fail, format!("can't read the database: {:#}", e));
+            return None;
+        }
+    };
+    let dirs = frecency.paths();
+    let last_visit = dirs
+        .iter()
+        .filter_map(|dir| frecency.visit_stats(dir))
+        .map(|stats| stats.last_visit)
+        .max();
+    frecency.set_kind(PathKind::File);
+    let files = frecency.paths().len();
+    report.check(
+        Status::Ok,
+        format!(
+            "read {} directories and {} files from {}",
+            dirs.len(),
+            files,
+            path.display()
+        ),
+    );
+    last_visit
+}
+
+fn check_shell(report: &mut Report, last_visit: Option<u64>) {
+    report.section("shell");
+    let shell = env::var("SHELL")
+        .ok()
+        .and_then(|shell| {
+            Path::new(&shell)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "<shell>".to_string());
+    let marker = env::var(PAZI_INIT_ENV!()).ok();
+    let parent = unsafe { libc::getppid() };
+    match marker.as_deref().and_then(|m| m.split_once(':')) {
+        Some((shell, pid)) if pid.parse() == Ok(parent) => {
+            report.check(Status::Ok, format!("pazi is initialized for {}", shell))
+        }
+        Some((shell, pid)) => {
+            report.check(
+                Status::Warn,
+                format!(
+                    "pazi was initialized for {} in another process (pid {}), but not in the \
+                     shell this was run from",
+                    shell, pid
+                ),
+            );
+            report.detail("this is expected if 'pazi doctor' wasn't run directly from a shell");
+        }
+        None => {
+            report.check(
+                Status::Fail,
+                "pazi isn't initialized in this shell, so directories aren't being recorded",
+            );
+            if shell == "fish" {
+                report.detail("add 'pazi init fish | source' to your config.fish");
+            } else {
+                report.detail(format!(
+                    "add 'eval \"$(pazi init {})\"' to your shell's rc file",
+                    shell
+                ));
+            }
+        }
+    }
+
+    // The hooks run whichever pazi is on PATH
+    let exe = env::current_exe().and_then(fs::canonicalize).ok();
+    match which::which("pazi") {
+        Ok(found) if fs::canonicalize(&found).ok() == exe || exe.is_none() => {
+            report.check(Status::Ok, format!("{} is on PATH", found.display()))
+        }
+        Ok(found) => report.check(
+            Status::Warn,
+            format!(
+                "the pazi on PATH, {}, isn't this one, {}; the shell hooks run that one",
+                found.display(),
+                exe.unwrap_or_default().display()
+            ),
+        ),
+        Err(_) => report.check(
+            Status::Fail,
+            "pazi isn't on PATH, so the shell hooks can't run it",
+        ),
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match last_visit.map(|visit| now.saturating_sub(visit)) {
+        Some(age) if age <= RECENT_VISIT.as_secs() => report.check(
+            Status::Ok,
+            format!("the last visit was recorded {}", format_age(age)),
+        ),
+        Some(age) => {
+            report.check(
+                Status::Warn,
+                format!("the last visit was recorded {}", format_age(age)),
+            );
+            report.detail(
+                "if you've changed directories since, visits aren't being recorded; check that \
+                 nothing in your rc file replaces pazi's hook, e.g. by overwriting PROMPT_COMMAND",
+            );
+        }
+        None => report.check(Status::Warn, "no visits have been recorded yet"),
+    }
+}
+
+// check_temp_files looks for temp files left next to the database by saves which were
+// interrupted, e.g. by the machine losing power.
+fn check_temp_files(report: &mut Report, db_path: &Path) {
+    report.section("temp files");
+    let stale = stale_temp_files(db_path, SystemTime::now());
+    if stale.is_empty() {
+        report.check(Status::Ok, "no stale temp files");
+        return;
+    }
+    report.check(
+        Status::Warn,
+        format!(
+            "{} temp files were left behind by interrupted saves, and may be deleted:",
+            stale.len()
+        ),
+    );
+    for path in stale {
+        report.detail(path.display());
+    }
+}
+
+// stale_temp_files returns the temp files 'save_to_disk' may have left next to the database,
+// named '.<db name>.<pid>', which are too old to belong to a save in progress.
+fn stale_temp_files(db_path: &Path, now: SystemTime) -> Vec<PathBuf> {
+    let (dir, name) = match (db_path.parent(), db_path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Vec::new(),
+    };
+    let prefix = format!(".{}.", name);
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut res: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            let is_temp_file = file_name
+                .strip_prefix(&prefix)
+                .is_some_and(|pid| !pid.is_empty() && pid.chars().all(|c| c.is_ascii_digit()));
+            let modified = entry.metadata().and_then(|m| m.modified());
+            is_temp_file
+                && modified.is_ok_and(|modified| {
+                    now.duration_since(modified).unwrap_or_default() > STALE_TEMP_FILE
+                })
+        })
+        .map(|entry| entry.path())
+        .collect();
+    res.sort();
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_stale_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("pazi_dirs.msgpack");
+        for name in &[
+            "pazi_dirs.msgpack",
+            ".pazi_dirs.msgpack.123",
+            ".pazi_dirs.msgpack.",
+            ".pazi_dirs.msgpack.12x",
+            ".other.msgpack.123",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        assert!(stale_temp_files(&db, SystemTime::now()).is_empty());
+        let later = SystemTime::now() + STALE_TEMP_FILE * 2;
+        assert_eq!(
+            stale_temp_files(&db, later),
+            vec![dir.path().join(".pazi_dirs.msgpack.123")]
+        );
+    }
+}
@@ -14,7 +14,11 @@ use super::frecent_paths::PathFrecencyDiff;
 
 // edit opens up EDITOR with the given input matches for the user to edit. It returns a 'diff' of
 // what has changed.
-pub fn edit(data: &[(String, f64)]) -> Result<PathFrecencyDiff> {
+// pins maps each pinned path to its name, if any, so that they may be marked for the user.
+pub fn edit(
+    data: &[(String, f64)],
+    pins: &HashMap<String, Option<String>>,
+) -> Result<PathFrecencyDiff> {
     let mut editor = env::var("PAZI_EDITOR")
         .or_else(|_| env::var("EDITOR"))
         .or_else(|_| env::var("VISUAL"))
@@ -47,7 +51,7 @@ pub fn edit(data: &[(String, f64)]) -> Result<PathFrecencyDiff> {
         .tempfile()
         .with_context(|| "error creating tempfile")?;
 
-    let serialized_data = serialize(data);
+    let serialized_data = serialize(data, pins);
     tmpf.write_all(serialized_data.as_bytes())
         .with_context(|| "could not write data to tempfile")?;
 
@@ -108,17 +112,27 @@ pub fn edit(data: &[(String, f64)]) -> Result<PathFrecencyDiff> {
     Ok(PathFrecencyDiff::new(additions, removals))
 }
 
-pub fn serialize(matches: &[(String, f64)]) -> String {
+pub fn serialize(matches: &[(String, f64)], pins: &HashMap<String, Option<String>>) -> String {
     format!(
         r#"# Edit your frecency fearlessly!
 #
 # Lines starting with '#' are comments. sh-esque quoting and escapes may be used in paths.
 # Columns are whitespace separated. The first column is the current score, the second is the path.
+# Pinned paths are preceded by a '# pinned' comment; use 'pazi pin' and 'pazi unpin' to change them.
 # Any changes saved here will be applied back to your frecency database immediately.
 {}"#,
         matches
             .iter()
-            .map(|(s, w)| format!("{}\t{}", w, snailquote::escape(s)))
+            .map(|(s, w)| {
+                let line = format!("{}\t{}", w, snailquote::escape(s));
+                match pins.get(s) {
+                    Some(Some(name)) => {
+                        format!("# pinned as {}\n{}", snailquote::escape(name), line)
+                    }
+                    Some(None) => format!("# pinned\n{}", line),
+                    None => line,
+                }
+            })
             .collect::<Vec<String>>()
             .join("\n")
     )
@@ -0,0 +1,119 @@
+// exclusions determines which paths should never be recorded in the frecency database, either
+// because they match a user-configured glob or because they're within a directory containing an
+// ignore marker file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+
+use crate::matcher::GLOB_OPTIONS;
+
+// A directory containing a file with this name is excluded, as is everything beneath it.
+pub const IGNORE_MARKER: &str = ".pazi-ignore";
+
+#[derive(Clone, Default)]
+pub struct Exclusions {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl Exclusions {
+    // load reads exclusion globs from the given file, one per line. Blank lines and lines
+    // starting with '#' are ignored, and a leading '~' is expanded to the home directory.
+    // A file which doesn't exist is treated as an empty one.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("could not read ignore file: {:?}", path))
+            }
+        };
+        Self::new(
+            data.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#')),
+        )
+        .with_context(|| format!("invalid ignore file: {:?}", path))
+    }
+
+    pub fn new<I, S>(patterns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let home = directories::UserDirs::new().map(|d| d.home_dir().to_path_buf());
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| {
+                let pattern = pattern.as_ref();
+                let expanded = match (pattern.strip_prefix('~'), &home) {
+                    (Some(rest), Some(home)) => format!("{}{}", home.to_string_lossy(), rest),
+                    _ => pattern.to_string(),
+                };
+                glob::Pattern::new(&expanded)
+                    .map_err(|e| anyhow!("invalid glob '{}': {}", pattern, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Exclusions { patterns })
+    }
+
+    // extend adds another set of exclusions to these
+    pub fn extend(&mut self, other: Exclusions) {
+        self.patterns.extend(other.patterns);
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if let Some(pattern) = self
+            .patterns
+            .iter()
+            .find(|p| p.matches_path_with(path, GLOB_OPTIONS))
+        {
+            debug!("{:?} is excluded by {}", path, pattern);
+            return true;
+        }
+        if let Some(dir) = path
+            .ancestors()
+            .find(|dir| dir.join(IGNORE_MARKER).exists())
+        {
+            debug!("{:?} is excluded by an ignore file in {:?}", path, dir);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn excludes_globs() {
+        let e = Exclusions::new(vec!["/", "/tmp/*", "/mnt/**", "**/target"]).unwrap();
+        assert!(e.is_excluded(Path::new("/")));
+        assert!(e.is_excluded(Path::new("/tmp/foo")));
+        // as with '-g', '*' doesn't match across separators, but '**' does
+        assert!(!e.is_excluded(Path::new("/tmp/foo/bar")));
+        assert!(e.is_excluded(Path::new("/mnt/foo/bar")));
+        assert!(e.is_excluded(Path::new("/home/user/dev/pazi/target")));
+        assert!(!e.is_excluded(Path::new("/tmp")));
+        assert!(!e.is_excluded(Path::new("/mnt")));
+        assert!(!e.is_excluded(Path::new("/home/user/dev/pazi")));
+        assert!(Exclusions::new(vec!["/tmp/[*"]).is_err());
+    }
+
+    #[test]
+    fn excludes_marked_subtrees() {
+        let root = tempfile::tempdir().unwrap();
+        let ignored = root.path().join("ignored");
+        fs::create_dir_all(ignored.join("child")).unwrap();
+        fs::File::create(ignored.join(IGNORE_MARKER)).unwrap();
+
+        let e = Exclusions::default();
+        assert!(!e.is_excluded(root.path()));
+        assert!(e.is_excluded(&ignored));
+        assert!(e.is_excluded(&ignored.join("child")));
+    }
+}
@@ -1,15 +1,25 @@
+use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::f64;
 use std::fmt;
 use std::hash::Hash;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use log::debug;
 use serde::{Deserialize, Serialize};
 
-const DECAY_RATE: f64 = f64::consts::LN_2 / (30. * 24. * 60. * 60.);
+// The default half-life of a visit; chosen to match mozilla's frecency
+const HALF_LIFE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+fn decay_rate(half_life: Duration) -> f64 {
+    f64::consts::LN_2 / half_life.as_secs_f64()
+}
+
+fn default_decay_rate() -> f64 {
+    decay_rate(HALF_LIFE)
+}
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Frecency<T>
@@ -20,6 +30,12 @@ where
     // is a much more frequent operation than searching through items for this program.
     frecency: HashMap<T, f64>,
     max_size: usize,
+    // Databases written before the decay rate was configurable all used the default half-life
+    #[serde(default = "default_decay_rate")]
+    decay_rate: f64,
+    // pinned keys are never trimmed to make room for new ones
+    #[serde(default)]
+    pinned: HashSet<T>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -37,9 +53,45 @@ where
     T: Hash + Eq + Ord + Clone + fmt::Debug,
 {
     pub fn new(max_size: usize) -> Self {
+        Self::with_half_life(max_size, HALF_LIFE)
+    }
+
+    pub fn with_half_life(max_size: usize, half_life: Duration) -> Self {
         Frecency {
             frecency: HashMap::new(),
             max_size,
+            decay_rate: decay_rate(half_life),
+            pinned: HashSet::new(),
+        }
+    }
+
+    // set_max_size changes how many keys are kept, trimming the least frecent ones if there are
+    // now too many.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        while self.frecency.len() > self.max_size {
+            if !self.trim_min() {
+                break;
+            }
+        }
+    }
+
+    // set_half_life changes how quickly visits decay from now on. Each key keeps the weight it has
+    // now.
+    pub fn set_half_life(&mut self, half_life: Duration) {
+        self.set_half_life_with_time(half_life, SystemTime::now())
+    }
+
+    fn set_half_life_with_time(&mut self, half_life: Duration, now: SystemTime) {
+        let rate = decay_rate(half_life);
+        if rate == self.decay_rate {
+            return;
+        }
+        let old_decay = self.now_decay(now);
+        self.decay_rate = rate;
+        let new_decay = self.now_decay(now);
+        for frecency in self.frecency.values_mut() {
+            *frecency += new_decay - old_decay;
         }
     }
 
@@ -51,9 +103,7 @@ where
     fn visit_with_time(&mut self, key: T, now: SystemTime) {
         // The only error here is if the system clock is before the unix epoch. I'm fine panicing
         // there.
-        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap();
-        let now_secs = since_epoch.as_secs() as f64 + since_epoch.subsec_nanos() as f64 * 1e-9;
-        let now_decay = now_secs * DECAY_RATE;
+        let now_decay = self.now_decay(now);
         debug!("upserting {:?}", key);
         match self.frecency.entry(key) {
             Entry::Occupied(mut e) => {
@@ -67,7 +117,57 @@ where
             }
         };
         while self.frecency.len() > self.max_size {
-            self.trim_min();
+            if !self.trim_min() {
+                // everything left is pinned
+                break;
+            }
+        }
+    }
+
+    fn now_decay(&self, now: SystemTime) -> f64 {
+        let since_epoch = now.duration_since(UNIX_EPOCH).unwrap();
+        let now_secs = since_epoch.as_secs() as f64 + since_epoch.subsec_nanos() as f64 * 1e-9;
+        now_secs * self.decay_rate
+    }
+
+    // weight returns the number of visits a key has had, with each visit decayed by its age. A key
+    // visited once just now has a weight of 1, and one visited twice a half-life ago also has a
+    // weight of 1.
+    pub fn weight(&self, key: &T) -> Option<f64> {
+        self.weight_with_time(key, SystemTime::now())
+    }
+
+    fn weight_with_time(&self, key: &T, now: SystemTime) -> Option<f64> {
+        self.frecency
+            .get(key)
+            .map(|frecency| (frecency - self.now_decay(now)).exp())
+    }
+
+    // value_with_weight returns the stored value of a key with the given weight, as if it had been
+    // visited that many times just now. It may be passed to 'overwrite'.
+    pub fn value_with_weight(&self, weight: f64) -> f64 {
+        self.value_with_weight_with_time(weight, SystemTime::now())
+    }
+
+    fn value_with_weight_with_time(&self, weight: f64, now: SystemTime) -> f64 {
+        weight.ln() + self.now_decay(now)
+    }
+
+    // add_weight adds the given weight to an existing key as if it had been visited that many
+    // additional times just now. It returns false if the key does not exist.
+    pub fn add_weight(&mut self, key: &T, weight: f64) -> bool {
+        self.add_weight_with_time(key, weight, SystemTime::now())
+    }
+
+    fn add_weight_with_time(&mut self, key: &T, weight: f64, now: SystemTime) -> bool {
+        let now_decay = self.now_decay(now);
+        match self.frecency.get_mut(key) {
+            Some(frecency) => {
+                *frecency = ((*frecency - now_decay).exp() + weight).ln() + now_decay;
+                debug!("Added {} to {:?}; changed to {}", weight, key, *frecency);
+                true
+            }
+            None => false,
         }
     }
 
@@ -85,10 +185,10 @@ where
         }
     }
 
-    fn trim_min(&mut self) {
+    fn trim_min(&mut self) -> bool {
         let min_key = {
             let mut min_entry = None;
-            for e in &self.frecency {
+            for e in self.frecency.iter().filter(|e| !self.pinned.contains(e.0)) {
                 min_entry = match min_entry {
                     None => Some(e),
                     Some(old_min) => {
@@ -103,19 +203,47 @@ where
             min_entry.map(|e| e.0.clone())
         };
 
-        if let Some(min) = min_key {
-            debug!("trimming: {:?}", min);
-            self.frecency.remove(&min);
+        match min_key {
+            Some(min) => {
+                debug!("trimming: {:?}", min);
+                self.frecency.remove(&min);
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn items(&self) -> FrecencyView<T, &HashMap<T, f64>> {
+    // pin marks a key as pinned, inserting it if it isn't already present.
+    pub fn pin(&mut self, key: T) {
+        // pin before inserting so a full database doesn't immediately trim the new key
+        self.pinned.insert(key.clone());
+        self.insert(key);
+    }
+
+    pub fn unpin(&mut self, key: &T) -> bool {
+        self.pinned.remove(key)
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.frecency.contains_key(key)
+    }
+
+    pub fn is_pinned<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.pinned.contains(key)
+    }
+
+    pub fn items(&self) -> FrecencyView<'_, T, &HashMap<T, f64>> {
         FrecencyView {
             items: &self.frecency,
         }
     }
 
     pub fn remove(&mut self, key: &T) -> Option<f64> {
+        self.pinned.remove(key);
         self.frecency.remove(key)
     }
 }
@@ -162,7 +290,6 @@ pub fn descending_frecency<T>(lhs: &(T, f64), rhs: &(T, f64)) -> Ordering {
 #[cfg(test)]
 mod test {
     use super::{Frecency, FrecencyView};
-    use std;
     use std::hash::Hash;
     use std::time;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -206,6 +333,24 @@ mod test {
         assert_eq!(keys(f.items()), vec!["bar", "baz"]);
     }
 
+    #[test]
+    fn never_trims_pinned() {
+        let mut f = Frecency::<&str>::new(2);
+        f.visit_with_time("foo", timef(10));
+        f.pinned.insert("foo");
+        f.visit_with_time("bar", timef(20));
+        f.visit_with_time("baz", timef(30));
+        assert_eq!(keys(f.items()), vec!["baz", "foo"]);
+
+        f.pinned.insert("baz");
+        f.visit_with_time("quux", timef(40));
+        assert_eq!(keys(f.items()), vec!["baz", "foo"]);
+        f.pin("quux");
+        assert_eq!(keys(f.items()), vec!["quux", "baz", "foo"]);
+        assert!(f.unpin(&"foo"));
+        assert!(!f.is_pinned(&"foo"));
+    }
+
     #[test]
     fn frecency_decay_works() {
         let mut f = Frecency::<&str>::new(5);
@@ -218,4 +363,61 @@ mod test {
         f.visit_with_time("bar", now);
         assert_eq!(keys(f.items()), vec!["bar", "foo"]);
     }
+
+    #[test]
+    fn adds_weight() {
+        let mut f = Frecency::<&str>::new(5);
+        f.visit_with_time("foo", timef(10));
+        f.visit_with_time("bar", timef(10));
+        f.visit_with_time("bar", timef(10));
+        assert_eq!(keys(f.items()), vec!["bar", "foo"]);
+        assert!(f.add_weight_with_time(&"foo", 1.5, timef(10)));
+        assert_eq!(keys(f.items()), vec!["foo", "bar"]);
+        let foo = f.weight_with_time(&"foo", timef(10)).unwrap();
+        assert!((foo - 2.5).abs() < 1e-6, "{}", foo);
+        assert!(!f.add_weight_with_time(&"baz", 1.0, timef(10)));
+    }
+
+    #[test]
+    fn weight_decays_with_half_life() {
+        let mut f = Frecency::<&str>::with_half_life(5, time::Duration::from_secs(100));
+        f.visit_with_time("foo", timef(1000));
+        f.visit_with_time("foo", timef(1000));
+        f.visit_with_time("bar", timef(1100));
+        let foo = f.weight_with_time(&"foo", timef(1100)).unwrap();
+        let bar = f.weight_with_time(&"bar", timef(1100)).unwrap();
+        assert!((foo - 1.0).abs() < 1e-6, "{}", foo);
+        assert!((bar - 1.0).abs() < 1e-6, "{}", bar);
+        assert_eq!(f.weight_with_time(&"baz", timef(1100)), None);
+    }
+
+    #[test]
+    fn overwriting_with_weight() {
+        let mut f = Frecency::<&str>::with_half_life(5, time::Duration::from_secs(100));
+        f.overwrite("foo", f.value_with_weight_with_time(3.0, timef(1000)));
+        let foo = f.weight_with_time(&"foo", timef(1100)).unwrap();
+        assert!((foo - 1.5).abs() < 1e-6, "{}", foo);
+    }
+
+    #[test]
+    fn changing_half_life_keeps_weights() {
+        let mut f = Frecency::<&str>::with_half_life(5, time::Duration::from_secs(100));
+        f.visit_with_time("foo", timef(1000));
+        f.set_half_life_with_time(time::Duration::from_secs(200), timef(1100));
+        let foo = f.weight_with_time(&"foo", timef(1100)).unwrap();
+        assert!((foo - 0.5).abs() < 1e-6, "{}", foo);
+        let foo = f.weight_with_time(&"foo", timef(1300)).unwrap();
+        assert!((foo - 0.25).abs() < 1e-6, "{}", foo);
+    }
+
+    #[test]
+    fn shrinking_max_size_trims() {
+        let mut f = Frecency::<&str>::new(5);
+        f.visit_with_time("foo", timef(10));
+        f.visit_with_time("foo", timef(10));
+        f.visit_with_time("bar", timef(10));
+        f.visit_with_time("baz", timef(10));
+        f.set_max_size(1);
+        assert_eq!(keys(f.items()), vec!["foo"]);
+    }
 }
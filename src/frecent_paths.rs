@@ -1,24 +1,177 @@
 // frecent_paths is a specialization of frecency that understands the semantics of stored paths.
 // It does things like the messyness of checking for a directory's existence and such.
 
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::vec::IntoIter;
 
 use anyhow::{bail, Context, Result};
 use log::debug;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
+use super::exclusions::Exclusions;
 use super::frecency::{descending_frecency, Frecency};
 use super::matcher::*;
+use super::prune;
+use super::query::Query;
+
+// How many query -> directory selections to remember
+const QUERY_MEMORY_SIZE: usize = 1000;
+// Selections decay faster than directory visits so that a query can be "re-taught" to resolve
+// somewhere else within a few days.
+const QUERY_MEMORY_HALF_LIFE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// A remembered selection for a longer query is a weaker signal than one for the same query.
+const QUERY_PREFIX_ATTENUATION: f64 = 0.5;
+// Time spent in a directory counts as an extra visit per DWELL_PER_VISIT, up to MAX_DWELL_VISITS
+// extra visits. This keeps directories which are only passed through from outranking ones which
+// are actually worked in.
+const DWELL_PER_VISIT: Duration = Duration::from_secs(10 * 60);
+const MAX_DWELL_VISITS: f64 = 3.0;
 
 #[derive(Clone)]
 pub struct PathFrecency {
-    frecency: Frecency<String>,
+    db: PathDb,
     // whether the frecency file is 'dirty' and should be updated on save
     dirty: bool,
     path: PathBuf,
+    // paths which should never be added to the database
+    exclusions: Exclusions,
+    // which kind of path in the database this operates on
+    kind: PathKind,
+    match_options: MatchOptions,
+    // index holds each path of the current kind prepared for matching. It's built on first use so
+    // that commands which never match anything don't pay for it.
+    index: OnceCell<HashMap<String, IndexedText>>,
+}
+
+// PathDb is the on-disk representation of a PathFrecency.
+// New fields must be added at the end and be marked 'serde(default)' so older databases can
+// still be read.
+#[derive(Clone, Deserialize, Serialize)]
+struct PathDb {
+    frecency: Frecency<String>,
+    // queries tracks which directory each query was resolved to by the user, keyed by
+    // (query, directory)
+    #[serde(default = "new_query_memory")]
+    queries: Frecency<(String, String)>,
+    // pin_names maps the name of a pinned directory to that directory
+    #[serde(default)]
+    pin_names: HashMap<String, String>,
+    #[serde(default = "new_path_frecency")]
+    files: Frecency<String>,
+    // entered tracks when each directory which hasn't been left yet was entered, in seconds since
+    // the unix epoch
+    #[serde(default)]
+    entered: HashMap<String, u64>,
+    // how often and when each directory and file was visited
+    #[serde(default)]
+    dir_stats: HashMap<String, VisitStats>,
+    #[serde(default)]
+    file_stats: HashMap<String, VisitStats>,
+}
+
+// VisitStats records how many times, and when last, a path was visited. It's only informational;
+// ranking is up to the frecency. Paths visited before it was recorded have none.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct VisitStats {
+    pub visits: u64,
+    // in seconds since the unix epoch
+    pub last_visit: u64,
+}
+
+fn new_query_memory() -> Frecency<(String, String)> {
+    Frecency::with_half_life(QUERY_MEMORY_SIZE, QUERY_MEMORY_HALF_LIFE)
+}
+
+fn new_path_frecency() -> Frecency<String> {
+    // remember 1000 entries total
+    Frecency::<String>::new(1000)
+}
+
+// PathKind is the kind of path a PathFrecency tracks. Each kind is tracked independently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathKind {
+    Directory,
+    File,
+}
+
+impl PathKind {
+    fn exists(self, path: &Path) -> bool {
+        match self {
+            PathKind::Directory => path.is_dir(),
+            PathKind::File => path.is_file(),
+        }
+    }
+}
+
+impl fmt::Display for PathKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathKind::Directory => write!(f, "directory"),
+            PathKind::File => write!(f, "file"),
+        }
+    }
+}
+
+impl PathDb {
+    fn new() -> Self {
+        PathDb {
+            frecency: new_path_frecency(),
+            queries: new_query_memory(),
+            pin_names: HashMap::new(),
+            files: new_path_frecency(),
+            entered: HashMap::new(),
+            dir_stats: HashMap::new(),
+            file_stats: HashMap::new(),
+        }
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self> {
+        match rmp_serde::from_slice(data) {
+            Ok(db) => Ok(db),
+            Err(e) => {
+                // Before query memory existed, the database was a bare 'Frecency'
+                debug!("could not read db, trying legacy format: {}", e);
+                let frecency = rmp_serde::from_slice(data)?;
+                Ok(PathDb {
+                    frecency,
+                    ..PathDb::new()
+                })
+            }
+        }
+    }
+}
+
+// Explanation describes how a path was scored for a query.
+pub struct Explanation {
+    pub path: String,
+    // the path's normalized frecency
+    pub frecency: f64,
+    pub pinned: bool,
+    // how strongly previously selecting this path for the query favors it, if it was
+    pub learned: Option<f64>,
+    // the score of each matcher which matched the path
+    pub matches: Vec<MatcherScore>,
+    // whether nothing matched any path, so the matches are from the typo-tolerant matchers
+    pub fallback: bool,
+    // the final score, as directory_matches would rank the path by
+    pub score: f64,
+    // whether the path still exists; paths which don't are trimmed rather than returned
+    pub exists: bool,
+}
+
+pub struct MatcherScore {
+    pub name: String,
+    // the innermost matcher's score, before any attenuation
+    pub raw: f64,
+    pub score: f64,
 }
 
 pub struct PathFrecencyDiff {
@@ -38,65 +191,391 @@ impl PathFrecencyDiff {
 impl PathFrecency {
     // load loads or, if it doesn't exist, creates a path frecency db at a given location
     pub fn load(path: &Path) -> Result<Self> {
-        let frecency_file = fs::OpenOptions::new()
+        let mut frecency_file = fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(path)
             .with_context(|| format!("could not open pazi frecency file: {:?}", path))?;
-        let metadata = frecency_file
-            .metadata()
-            .with_context(|| format!("could not get pazi frecency file metadata: {:?}", path))?;
-        // remember 1000 entries total
-        let frecency = if metadata.len() > 0 {
+        let mut data = Vec::new();
+        frecency_file
+            .read_to_end(&mut data)
+            .with_context(|| format!("could not read pazi frecency file: {:?}", path))?;
+        let db = if !data.is_empty() {
             // existing file, unmarshal that sucker
-            let mut de = rmp_serde::Deserializer::new(frecency_file);
-            serde::Deserialize::deserialize(&mut de).with_context(|| {
+            PathDb::deserialize(&data).with_context(|| {
                 format!(
                     "could not deserialize pazi frecency file as valid msgpack data: {:?}",
                     path
                 )
             })?
         } else {
-            Frecency::<String>::new(1000)
+            PathDb::new()
         };
 
         Ok(PathFrecency {
-            frecency,
+            db,
             path: path.to_path_buf(),
             dirty: false,
+            exclusions: Exclusions::default(),
+            kind: PathKind::Directory,
+            match_options: MatchOptions::default(),
+            index: OnceCell::new(),
         })
     }
 
+    pub fn set_exclusions(&mut self, exclusions: Exclusions) {
+        self.exclusions = exclusions;
+    }
+
+    // set_limits sets how many directories and files are remembered, and how quickly visits to
+    // them decay.
+    pub fn set_limits(&mut self, max_size: usize, half_life: Duration) {
+        for entries in [&mut self.db.frecency, &mut self.db.files] {
+            entries.set_max_size(max_size);
+            entries.set_half_life(half_life);
+        }
+    }
+
+    pub fn set_match_options(&mut self, options: MatchOptions) {
+        self.match_options = options;
+    }
+
+    // set_kind selects which kind of path subsequent operations apply to. Directories are
+    // selected by default.
+    pub fn set_kind(&mut self, kind: PathKind) {
+        self.kind = kind;
+        self.index = OnceCell::new();
+    }
+
+    fn entries(&self) -> &Frecency<String> {
+        match self.kind {
+            PathKind::Directory => &self.db.frecency,
+            PathKind::File => &self.db.files,
+        }
+    }
+
+    // indexed returns the match index entry for a path. Paths added since the index was built are
+    // indexed on the fly.
+    fn indexed<'a>(&'a self, path: &str) -> Cow<'a, IndexedText> {
+        let index = self.index.get_or_init(|| {
+            self.entries()
+                .items()
+                .raw()
+                .into_iter()
+                .map(|(path, _)| (path.clone(), IndexedText::new(path)))
+                .collect()
+        });
+        match index.get(path) {
+            Some(indexed) => Cow::Borrowed(indexed),
+            None => Cow::Owned(IndexedText::new(path)),
+        }
+    }
+
+    fn entries_mut(&mut self) -> &mut Frecency<String> {
+        match self.kind {
+            PathKind::Directory => &mut self.db.frecency,
+            PathKind::File => &mut self.db.files,
+        }
+    }
+
     pub fn visit(&mut self, dir: String) {
-        self.frecency.visit(dir);
+        if self.exclusions.is_excluded(Path::new(&dir)) {
+            debug!("not visiting excluded path: {}", dir);
+            return;
+        }
+        let now = unix_now();
+        if self.kind == PathKind::Directory {
+            self.db.entered.insert(dir.clone(), now);
+        }
+        let stats = self.stats_mut().entry(dir.clone()).or_default();
+        stats.visits += 1;
+        stats.last_visit = now;
+        self.entries_mut().visit(dir);
+        // the visit may have trimmed another path to make room
+        self.forget_removed_stats();
         self.dirty = true
     }
 
+    pub fn visit_stats(&self, path: &str) -> Option<VisitStats> {
+        let stats = match self.kind {
+            PathKind::Directory => &self.db.dir_stats,
+            PathKind::File => &self.db.file_stats,
+        };
+        stats.get(path).copied()
+    }
+
+    fn stats_mut(&mut self) -> &mut HashMap<String, VisitStats> {
+        match self.kind {
+            PathKind::Directory => &mut self.db.dir_stats,
+            PathKind::File => &mut self.db.file_stats,
+        }
+    }
+
+    // forget_removed_stats drops the stats of paths which are no longer in the database
+    fn forget_removed_stats(&mut self) {
+        let (entries, stats) = match self.kind {
+            PathKind::Directory => (&self.db.frecency, &mut self.db.dir_stats),
+            PathKind::File => (&self.db.files, &mut self.db.file_stats),
+        };
+        stats.retain(|path, _| entries.contains(path));
+    }
+
+    // leave records that a directory was left, weighting its most recent visit by how long was
+    // spent in it.
+    pub fn leave(&mut self, dir: &str) {
+        let frecency = &self.db.frecency;
+        // directories which were trimmed or excluded can't be left
+        self.db.entered.retain(|d, _| frecency.contains(d));
+
+        let entered = match self.db.entered.remove(dir) {
+            Some(entered) => entered,
+            None => {
+                debug!("left {} without having entered it", dir);
+                return;
+            }
+        };
+        self.dirty = true;
+        let dwell = unix_now().saturating_sub(entered) as f64;
+        let weight = (dwell / DWELL_PER_VISIT.as_secs_f64()).min(MAX_DWELL_VISITS);
+        debug!("spent {}s in {}; adding {}", dwell, dir, weight);
+        if weight > 0.0 {
+            self.db.frecency.add_weight(&dir.to_string(), weight);
+        }
+    }
+
+    // record_selection remembers that the given query was resolved to the given directory, be it
+    // by the matcher, an interactive selection, or a pipe program.
+    pub fn record_selection(&mut self, query: &str, dir: &str) {
+        let query = self.normalize_query(query);
+        if query.is_empty() {
+            return;
+        }
+        self.db.queries.visit((query, dir.to_string()));
+        self.dirty = true
+    }
+
+    // normalize_query strips a query's operators so that, e.g., '^foo' and 'foo' are learned
+    // together.
+    fn normalize_query(&self, query: &str) -> String {
+        Query::parse(query, self.match_options.smart_case).normalized()
+    }
+
+    // pin pins a directory so it is never trimmed and always ranks as if it were the most frecent
+    // entry. If a name is given, the directory may also be jumped to by that exact name.
+    pub fn pin(&mut self, dir: String, name: Option<&str>) {
+        if let Some(name) = name {
+            self.db.pin_names.insert(name.to_string(), dir.clone());
+        }
+        self.entries_mut().pin(dir);
+        self.dirty = true
+    }
+
+    // unpin unpins the directory pinned with the given name, or the given directory. It returns
+    // the directory that was unpinned, if any.
+    pub fn unpin(&mut self, target: &str) -> Option<String> {
+        let dir = match self.db.pin_names.get(target) {
+            Some(dir) => dir.clone(),
+            None => target.to_string(),
+        };
+        if !self.entries_mut().unpin(&dir) {
+            return None;
+        }
+        self.db.pin_names.retain(|_, d| *d != dir);
+        self.dirty = true;
+        Some(dir)
+    }
+
+    pub fn pinned_dir_named(&self, name: &str) -> Option<&str> {
+        self.db.pin_names.get(name).map(|dir| dir.as_str())
+    }
+
+    // pins returns every pinned directory along with its name, if it has one.
+    pub fn pins(&self) -> HashMap<String, Option<String>> {
+        let mut pins: HashMap<_, _> = self
+            .entries()
+            .items()
+            .raw()
+            .into_iter()
+            .filter(|(dir, _)| self.entries().is_pinned(*dir))
+            .map(|(dir, _)| (dir.clone(), None))
+            .collect();
+        // Only directories may be named
+        if self.kind == PathKind::Directory {
+            for (name, dir) in &self.db.pin_names {
+                pins.insert(dir.clone(), Some(name.clone()));
+            }
+        }
+        pins
+    }
+
+    // normalized_items returns the normalized frecency of each directory, with pinned directories
+    // scored as highly as the most frecent one and sorted before everything else.
+    fn normalized_items(&self) -> Vec<(&String, f64)> {
+        let mut items = self.entries().items().normalized();
+        for item in items.iter_mut() {
+            if self.entries().is_pinned(item.0) {
+                item.1 = 1.0;
+            }
+        }
+        items.sort_by(|lhs, rhs| self.pinned_first(lhs, rhs));
+        items
+    }
+
+    fn pinned_first<T: AsRef<str>>(&self, lhs: &(T, f64), rhs: &(T, f64)) -> Ordering {
+        let lhs_pinned = self.entries().is_pinned(lhs.0.as_ref());
+        let rhs_pinned = self.entries().is_pinned(rhs.0.as_ref());
+        rhs_pinned
+            .cmp(&lhs_pinned)
+            .then_with(|| descending_frecency(lhs, rhs))
+    }
+
+    // learned_matches returns, for each directory a query (or a longer query it is a prefix of)
+    // was previously resolved to, how strongly that selection should be favored, from 0 to 1.
+    fn learned_matches(&self, query: &str) -> HashMap<String, f64> {
+        let mut res: HashMap<String, f64> = HashMap::new();
+        let query = self.normalize_query(query);
+        if query.is_empty() {
+            return res;
+        }
+        for (key, _) in self.db.queries.items().raw() {
+            let (q, dir) = key;
+            let attenuation = if *q == query {
+                1.0
+            } else if q.starts_with(&query) {
+                QUERY_PREFIX_ATTENUATION
+            } else {
+                continue;
+            };
+            let weight = match self.db.queries.weight(key) {
+                Some(w) => w,
+                None => continue,
+            };
+            // Map the number of (decayed) selections onto [0, 1), such that a single recent
+            // selection is worth 0.5.
+            let strength = attenuation * weight / (weight + 1.0);
+            let entry = res.entry(dir.clone()).or_insert(0.0);
+            if strength > *entry {
+                *entry = strength;
+            }
+        }
+        res
+    }
+
     pub fn maybe_add_relative_to(&mut self, mut base_path: PathBuf, relative_path: &str) -> bool {
-        // If the path exists, add it to the database
         base_path.push(relative_path);
         fs::canonicalize(base_path)
-            .map(|path| {
-                if path.is_dir() {
-                    path.to_str()
-                        .map(|path_str| {
-                            debug!("Visited path exists: {}", path_str);
-                            self.frecency.insert(path_str.to_owned());
-                            self.dirty = true;
-                            true
-                        })
-                        .unwrap_or(false)
-                } else {
-                    false
-                }
+            .map(|path| self.maybe_add(&path))
+            .unwrap_or(false)
+    }
+
+    // maybe_add adds a path to the database if it exists and isn't excluded. A path which is
+    // already in the database is left as is.
+    pub fn maybe_add(&mut self, path: &Path) -> bool {
+        if !self.kind.exists(path) || self.exclusions.is_excluded(path) {
+            return false;
+        }
+        path.to_str()
+            .map(|path_str| {
+                debug!("Visited path exists: {}", path_str);
+                self.entries_mut().insert(path_str.to_owned());
+                self.dirty = true;
+                true
             })
             .unwrap_or(false)
     }
 
+    // prunable returns every path in the database which 'criteria' selects for pruning, along
+    // with why, sorted by path. Paths which no longer exist, or are no longer the kind of path
+    // tracked, are always selected. Pinned paths are only selected if they're excluded; like
+    // 'trim', they're kept when missing in case they're on a drive which isn't mounted.
+    pub fn prunable(&self, criteria: &prune::Criteria) -> Vec<(String, prune::Reason)> {
+        let now = unix_now();
+        self.paths()
+            .into_iter()
+            .filter_map(|path| {
+                let pinned = self.entries().is_pinned(&path);
+                let reason = if criteria.excluded && self.exclusions.is_excluded(Path::new(&path)) {
+                    prune::Reason::Excluded
+                } else if pinned {
+                    return None;
+                } else if !self.kind.exists(Path::new(&path)) {
+                    match fs::metadata(&path) {
+                        Ok(_) => prune::Reason::WrongKind(self.kind.to_string()),
+                        Err(_) => prune::Reason::Missing,
+                    }
+                } else {
+                    let age = self
+                        .visit_stats(&path)
+                        .map(|stats| now.saturating_sub(stats.last_visit));
+                    let weight = self.entries().weight(&path).unwrap_or(0.0);
+                    match (criteria.older_than, age, criteria.below_weight) {
+                        (Some(older_than), Some(age), _) if age > older_than.as_secs() => {
+                            prune::Reason::NotVisitedFor(age)
+                        }
+                        (_, _, Some(below)) if weight < below => prune::Reason::LowWeight(weight),
+                        _ => return None,
+                    }
+                };
+                Some((path, reason))
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries().contains(&path.to_string())
+    }
+
+    // paths returns every path in the database, sorted
+    // substring_matches returns every path containing each term of a query, sorted by path. Case
+    // is ignored the same way it is for directory_matches, but nothing is matched fuzzily, which
+    // makes it safe to act on every match at once.
+    pub fn substring_matches(&self, filter: &str) -> Vec<String> {
+        let query = Query::parse(filter, self.match_options.smart_case);
+        self.paths()
+            .into_iter()
+            .filter(|path| query.is_contained_in(&self.indexed(path).text()))
+            .collect()
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<_> = self
+            .entries()
+            .items()
+            .raw()
+            .into_iter()
+            .map(|(p, _)| p.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.exclusions.is_excluded(path)
+    }
+
+    // score_for_visits returns the raw score, as used in a diff, of a path visited the given
+    // number of times just now.
+    pub fn score_for_visits(&self, visits: f64) -> f64 {
+        self.entries().value_with_weight(visits)
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        if self.entries_mut().remove(&path.to_string()).is_none() {
+            return false;
+        }
+        self.db.pin_names.retain(|_, dir| dir != path);
+        self.stats_mut().remove(path);
+        self.dirty = true;
+        true
+    }
+
     pub fn apply_diff(&mut self, diff: PathFrecencyDiff) -> Result<()> {
+        // An edited score is a removal and an addition, which shouldn't lose the pin
+        let pins = self.pins();
         for removal in diff.removals {
-            match self.frecency.remove(&removal) {
+            match self.entries_mut().remove(&removal) {
                 Some(_) => {}
                 None => {
                     bail!("no such item to remove: {}", removal);
@@ -106,10 +585,20 @@ impl PathFrecency {
         }
 
         for addition in diff.additions {
-            self.frecency.overwrite(addition.0, addition.1);
+            self.entries_mut().overwrite(addition.0, addition.1);
             self.dirty = true;
         }
 
+        for dir in pins.into_keys() {
+            if self.entries().contains(&dir) {
+                self.entries_mut().pin(dir);
+            }
+        }
+        let frecency = &self.db.frecency;
+        self.db.pin_names.retain(|_, dir| frecency.contains(dir));
+        // An edited score keeps its stats, since it's a removal and an addition
+        self.forget_removed_stats();
+
         Ok(())
     }
 
@@ -141,7 +630,7 @@ impl PathFrecency {
         let tmpfile =
             fs::File::create(&tmpfile_path).with_context(|| "could not create tempfile")?;
 
-        self.frecency
+        self.db
             .serialize(&mut rmp_serde::Serializer::new(tmpfile))
             .with_context(|| "could not serialize frecency to tempfile")?;
         fs::rename(&tmpfile_path, &self.path).with_context(|| {
@@ -152,20 +641,18 @@ impl PathFrecency {
         })
     }
 
-    pub fn items_with_frecency(&mut self) -> FrecentPathIter {
+    pub fn items_with_frecency(&mut self) -> FrecentPathIter<'_> {
         let items = self
-            .frecency
-            .items()
-            .normalized()
+            .normalized_items()
             .into_iter()
             .map(|(p, f)| (p.to_owned(), f))
             .collect::<Vec<_>>();
         FrecentPathIter::new(self, items)
     }
 
-    pub fn items_with_frecency_raw(&mut self) -> FrecentPathIter {
+    pub fn items_with_frecency_raw(&mut self) -> FrecentPathIter<'_> {
         let mut items = self
-            .frecency
+            .entries()
             .items()
             .raw()
             .into_iter()
@@ -176,126 +663,243 @@ impl PathFrecency {
     }
 
     pub fn directory_matches<'a>(&'a mut self, filter: &str) -> FrecentPathIter<'a> {
-        self.directory_matches_impl(filter, true, |item, weight, match_weight| {
-            (item, match_weight * 0.8 + weight * 0.2)
-        })
+        let learned = self.learned_matches(filter);
+        let match_ratio = self.match_options.weights.match_ratio;
+        self.directory_matches_impl(filter, true, learned, match_ratio)
     }
 
     pub fn directory_matches_raw<'a>(&'a mut self, filter: &str) -> FrecentPathIter<'a> {
-        self.directory_matches_impl(filter, false, |item, weight, _| (item, weight))
+        self.directory_matches_impl(filter, false, HashMap::new(), 0.0)
     }
 
     fn directory_matches_impl<'a>(
         &'a mut self,
         filter: &str,
         normalize: bool,
-        weight: fn(item: &String, frecency: f64, match_weight: f64) -> (&String, f64),
+        learned: HashMap<String, f64>,
+        match_ratio: f64,
     ) -> FrecentPathIter<'a> {
-        // 'best directory' is a tricky concept, as is 'match.
-        //
-        // There's a continuum from "exact string match" to "no characters in common", and we
-        // have to try and approximate what a human expects to figure out the weight and cutoff
-        // within that continuum.
-        //
-        // The following assumptions are what I started with:
-        // 1) Exact matches should always be jumped to with no questions asked. Exact matches are
-        //    rare. Substring matches are permissible and expected.
-        // 2) Components should be deconstructed from frecency database items for matching; people
-        //    think in components. For example, an entry of "/home/user/dev" will be thought about
-        //    by a user as the three distinct components "home", "user", and "dev", so we can
-        //    better match their expectations by matching individual components.
-        // 3) Component matches should be weighted based on how "deep" / "far right" the matched
-        //    component is. That is to say, the query "foo" should be weighted more highly for
-        //    "/home/user/project/foo" than for "/home/user/foo/stuff", even if the latter is
-        //    higher in the frecency index.
-        // 4) Case and punctuation in the target are liable to not be present in the query.
-        // 5) If the query contains a component separator, the user likely wants each side of it to
-        //    be fuzzy. That is to say: "z dev/tool" likely wishes to do a fuzzy match on the
-        //    strings "dev" and "tool" on adjacent components, leading to results like
-        //    "dev/my-tool" being possible.
-        // 6) Levenshtein distance may be fallen back upon for real "fuzzyness", but should be
-        //    weighted carefully low; sometimes it is better to force a user to make a new query
-        //    than to make too strange of a shot in the dark.
-        let em = ExactMatcher {};
-        let sm = SubstringMatcher {};
-        let ci_em = CaseInsensitiveMatcher::new(&em);
-        let pc_em = PathComponentMatcher::new(&em);
-        let pc_sm = PathComponentMatcher::new(&sm);
-        let pc_ci_em = PathComponentMatcher::new(&ci_em);
-        let ci_sm = CaseInsensitiveMatcher::new(&sm);
-        let pc_ci_sm = PathComponentMatcher::new(&ci_sm);
-        let matchers: Vec<&dyn Matcher> = vec![
-            &ExactMatcher {},
-            &ci_em,
-            &pc_em,
-            &pc_sm,
-            &pc_ci_em,
-            &SubstringMatcher {},
-            &ci_sm,
-            &pc_ci_sm,
-        ];
-
-        let mut dedupe_map: HashMap<String, f64> = HashMap::new();
-
-        // Run each matcher on each path
+        let query = Query::parse(filter, self.match_options.smart_case);
+        let weights = self.match_options.weights;
+        with_matchers(query.ignore_case(), &weights, |tiers, fallback_matchers| {
+            self.rank_matches(
+                tiers,
+                fallback_matchers,
+                &query,
+                normalize,
+                learned,
+                match_ratio,
+            )
+        })
+    }
+
+    // explain scores every directory the way directory_matches would, but keeps the details of
+    // how each score was arrived at, including for directories which no longer exist.
+    pub fn explain(&self, filter: &str) -> Vec<Explanation> {
+        let query = Query::parse(filter, self.match_options.smart_case);
+        let learned = self.learned_matches(filter);
+        let weights = self.match_options.weights;
+        let search = IndexedText::new(&query.text());
+        let items: Vec<_> = self
+            .normalized_items()
+            .into_iter()
+            .map(|(item, frecency)| (item, frecency, self.indexed(item)))
+            .filter(|(_, _, indexed)| query.permits(&indexed.text()))
+            .collect();
+
+        let mut res: Vec<Explanation> =
+            with_matchers(query.ignore_case(), &weights, |tiers, fallback_matchers| {
+                let explain_all = |matchers: &[&dyn Matcher], item: &IndexedText| {
+                    matchers
+                        .iter()
+                        .filter_map(|m| {
+                            m.explain_text(&item.text(), &search.text())
+                                .map(|(raw, score)| MatcherScore {
+                                    name: m.name(),
+                                    raw,
+                                    score,
+                                })
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let mut explained: Vec<_> = par_map(&items, |(item, frecency, indexed)| {
+                    let matches = tiers
+                        .iter()
+                        .map(|matchers| explain_all(matchers, indexed))
+                        .find(|matches| !matches.is_empty())
+                        .unwrap_or_default();
+                    (*item, *frecency, matches)
+                });
+                let fallback = explained.iter().all(|(_, _, m)| m.is_empty());
+                if fallback {
+                    for ((_, _, m), (_, _, indexed)) in explained.iter_mut().zip(&items) {
+                        *m = explain_all(fallback_matchers, indexed);
+                    }
+                }
+                explained
+                    .into_iter()
+                    .filter_map(|(item, frecency, matches)| {
+                        let best_match = matches.iter().map(|m| m.score).reduce(f64::max);
+                        let learned = learned.get(item).copied();
+                        let score =
+                            combined_score(best_match, frecency, learned, weights.match_ratio)?;
+                        Some(Explanation {
+                            path: item.to_owned(),
+                            frecency,
+                            pinned: self.entries().is_pinned(item),
+                            learned,
+                            matches,
+                            fallback,
+                            score,
+                            exists: self.kind.exists(Path::new(item)),
+                        })
+                    })
+                    .collect()
+            });
+        res.sort_by(|lhs, rhs| self.pinned_first(&(&lhs.path, lhs.score), &(&rhs.path, rhs.score)));
+        res
+    }
+
+    // pattern_matches returns the paths matching a pattern matcher, such as a RegexMatcher, ranked
+    // the same way as directory_matches ranks fuzzy matches.
+    pub fn pattern_matches<'a>(&'a mut self, pattern: &dyn Matcher) -> FrecentPathIter<'a> {
+        self.rank_matches(
+            &[&[pattern]],
+            &[],
+            &Query::default(),
+            true,
+            HashMap::new(),
+            self.match_options.weights.match_ratio,
+        )
+    }
+
+    pub fn pattern_matches_raw<'a>(&'a mut self, pattern: &dyn Matcher) -> FrecentPathIter<'a> {
+        self.rank_matches(
+            &[&[pattern]],
+            &[],
+            &Query::default(),
+            false,
+            HashMap::new(),
+            0.0,
+        )
+    }
+
+    // rank_matches scores every path the query permits with the best of the matchers in the
+    // first tier of them to match it (see 'with_matchers'), consulting the fallback matchers only
+    // if nothing matched, and combines that with its frecency, 'match_ratio' of the score coming
+    // from the match.
+    fn rank_matches<'a>(
+        &'a mut self,
+        tiers: &[&[&dyn Matcher]],
+        fallback_matchers: &[&dyn Matcher],
+        query: &Query,
+        normalize: bool,
+        learned: HashMap<String, f64>,
+        match_ratio: f64,
+    ) -> FrecentPathIter<'a> {
+        let filter = &query.text();
         let items = if normalize {
-            self.frecency.items().normalized()
+            self.normalized_items()
         } else {
-            self.frecency.items().raw()
+            self.entries().items().raw()
         };
-        let matched = items.iter().flat_map(|item| {
-            matchers.iter().filter_map(move |m| {
-                m.matches(item.0, filter)
-                    .map(move |v| weight(item.0, item.1, v))
-            })
-        });
 
-        // Remove dupe paths, keeping only each with the highest score
-        for el in matched {
-            match dedupe_map.get_mut(el.0) {
-                Some(val) => {
-                    if el.1 > *val {
-                        *val = el.1
-                    }
-                }
-                None => {
-                    dedupe_map.insert(el.0.to_owned(), el.1);
-                }
-            };
-        }
+        let search = IndexedText::new(filter);
+        let items: Vec<_> = items
+            .into_iter()
+            .map(|(item, frecency)| (item, frecency, self.indexed(item)))
+            .filter(|(_, _, indexed)| query.permits(&indexed.text()))
+            .collect();
 
-        let mut deduped: Vec<_> = dedupe_map.into_iter().collect();
-        deduped.sort_by(descending_frecency);
+        // Run each matcher on each path, keeping only the highest score for each
+        let best_match = |matchers: &[&dyn Matcher], item: &IndexedText| {
+            matchers
+                .iter()
+                .filter_map(|m| {
+                    m.explain_text(&item.text(), &search.text())
+                        .map(|(_, score)| score)
+                })
+                .fold(None, |acc: Option<f64>, v| match acc {
+                    Some(existing) if existing >= v => Some(existing),
+                    _ => Some(v),
+                })
+        };
+        let mut scored: Vec<_> = par_map(&items, |(item, frecency, indexed)| {
+            let best = tiers
+                .iter()
+                .find_map(|matchers| best_match(matchers, indexed));
+            (*item, *frecency, best)
+        });
+        if !fallback_matchers.is_empty() && scored.iter().all(|(_, _, m)| m.is_none()) {
+            debug!(
+                "no matches for {}, falling back to typo-tolerant matching",
+                filter
+            );
+            for ((_, _, m), (_, _, indexed)) in scored.iter_mut().zip(&items) {
+                *m = best_match(fallback_matchers, indexed);
+            }
+        }
+        let mut matched: Vec<_> = scored
+            .into_iter()
+            .filter_map(|(item, frecency, best_match)| {
+                let learned_weight = learned.get(item).copied();
+                combined_score(best_match, frecency, learned_weight, match_ratio)
+                    .map(|score| (item.to_owned(), score))
+            })
+            .collect();
+        if normalize {
+            matched.sort_by(|lhs, rhs| self.pinned_first(lhs, rhs));
+        } else {
+            matched.sort_by(descending_frecency);
+        }
 
         debug!(
             "{}",
-            deduped
+            matched
                 .iter()
                 .fold("Matched paths:".to_string(), |acc, el| {
                     acc + &format!("\n{} with score {}", el.0, el.1)
                 })
         );
 
-        FrecentPathIter::new(self, deduped)
+        FrecentPathIter::new(self, matched)
     }
 
     pub fn trim(&mut self, path: &str) -> bool {
-        if Path::new(path).is_dir() {
+        if self.kind.exists(Path::new(path)) {
             false
+        } else if self.entries().is_pinned(path) {
+            // Pinned directories are kept around even if they're temporarily missing, e.g.
+            // because they're on an unmounted drive, but they can't be jumped to right now.
+            debug!("skipping nonexistent pinned {}: {}", self.kind, path);
+            true
         } else {
-            debug!("trimming nonexistent dir: {}", path);
-            self.frecency.remove(&path.to_string());
+            debug!("trimming nonexistent {}: {}", self.kind, path);
+            self.entries_mut().remove(&path.to_string());
+            self.stats_mut().remove(path);
             self.dirty = true;
             true
         }
     }
 }
 
+// Missing is what a FrecentPathIter does with paths which no longer exist
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Missing {
+    // remove them from the database and skip them
+    Trim,
+    // skip them, leaving the database as it is
+    Skip,
+    // yield them like any other path
+    Keep,
+}
+
 /// An owning iterator over frecent paths
-/// which removes nonexistent directories from the database.
+/// which, by default, removes nonexistent directories from the database.
 pub struct FrecentPathIter<'a> {
     db: &'a mut PathFrecency,
     paths: IntoIter<(String, f64)>,
+    missing: Missing,
 }
 
 impl<'a> FrecentPathIter<'a> {
@@ -303,8 +907,14 @@ impl<'a> FrecentPathIter<'a> {
         FrecentPathIter {
             db,
             paths: paths.into_iter(),
+            missing: Missing::Trim,
         }
     }
+
+    pub fn missing(mut self, missing: Missing) -> Self {
+        self.missing = missing;
+        self
+    }
 }
 
 impl<'a> Iterator for FrecentPathIter<'a> {
@@ -313,9 +923,479 @@ impl<'a> Iterator for FrecentPathIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let el = self.paths.next()?;
-            if !self.db.trim(&el.0) {
+            let skip = match self.missing {
+                Missing::Trim => self.db.trim(&el.0),
+                Missing::Skip => !self.db.kind.exists(Path::new(&el.0)),
+                Missing::Keep => false,
+            };
+            if !skip {
                 return Some(el);
             }
         }
     }
 }
+
+// with_matchers calls 'f' with the tiers of matchers every query is scored with, followed by the
+// matchers which are only consulted if none of those match anything. Each path is scored by the
+// first tier with a matcher matching it; later tiers are slower, and only worth running for paths
+// the earlier ones missed. Case-insensitive matchers are only included if 'ignore_case' is set.
+fn with_matchers<R>(
+    ignore_case: bool,
+    weights: &MatchWeights,
+    f: impl FnOnce(&[&[&dyn Matcher]], &[&dyn Matcher]) -> R,
+) -> R {
+    // 'best directory' is a tricky concept, as is 'match.
+    //
+    // There's a continuum from "exact string match" to "no characters in common", and we
+    // have to try and approximate what a human expects to figure out the weight and cutoff
+    // within that continuum.
+    //
+    // The following assumptions are what I started with:
+    // 1) Exact matches should always be jumped to with no questions asked. Exact matches are
+    //    rare. Substring matches are permissible and expected.
+    // 2) Components should be deconstructed from frecency database items for matching; people
+    //    think in components. For example, an entry of "/home/user/dev" will be thought about
+    //    by a user as the three distinct components "home", "user", and "dev", so we can
+    //    better match their expectations by matching individual components.
+    // 3) Component matches should be weighted based on how "deep" / "far right" the matched
+    //    component is. That is to say, the query "foo" should be weighted more highly for
+    //    "/home/user/project/foo" than for "/home/user/foo/stuff", even if the latter is
+    //    higher in the frecency index.
+    // 4) Case and punctuation in the target are liable to not be present in the query.
+    // 5) If the query contains a component separator, the user likely wants each side of it to
+    //    be fuzzy. That is to say: "z dev/tool" likely wishes to do a fuzzy match on the
+    //    strings "dev" and "tool" on adjacent components, leading to results like
+    //    "dev/my-tool" being possible.
+    // 6) Levenshtein distance may be fallen back upon for real "fuzzyness", but should be
+    //    weighted carefully low; sometimes it is better to force a user to make a new query
+    //    than to make too strange of a shot in the dark. It's only used when nothing else
+    //    matched at all.
+    // 7) A directory the user previously picked for this query (or one it's a prefix of) is
+    //    very likely what they want again, so it is pulled towards the top regardless of
+    //    how well it matches textually.
+    // 8) A query of several terms, e.g. "z work api", is a list of things the user remembers
+    //    about the path in order from left to right. The last term is what they remember
+    //    about the directory itself, so it must match the final component.
+    // 9) Sometimes the user knows exactly where a term should match, or which paths they
+    //    don't want; query operators (see the query module) let them say so, and paths which
+    //    don't satisfy them aren't considered at all.
+    // 10) Long names made up of several words are often remembered by their initials, e.g.
+    //    "gep" for "github-enterprise-provisioning".
+    let (start, end) = (
+        weights.component_falloff_start,
+        weights.component_falloff_end,
+    );
+    let ci = |m| CaseInsensitiveMatcher::new(m).with_attenuation(weights.case_insensitive);
+    let pc = |m| PathComponentMatcher::new(m).with_falloff(start, end);
+    let ac = |m| AdjacentComponentMatcher::new(m).with_falloff(start, end);
+    let em = ExactMatcher {};
+    let sm = SubstringMatcher {
+        non_prefix_weight: weights.non_prefix_substring,
+    };
+    let ci_em = ci(&em);
+    let pc_em = pc(&em);
+    let pc_sm = pc(&sm);
+    let pc_ci_em = pc(&ci_em);
+    let ci_sm = ci(&sm);
+    let pc_ci_sm = pc(&ci_sm);
+    let mt = MultiTermMatcher {
+        non_prefix_weight: weights.non_prefix_substring,
+    };
+    let ci_mt = ci(&mt);
+    let ss = SubsequenceMatcher { ignore_case };
+    let pc_ss = pc(&ss);
+    let am = AcronymMatcher {
+        ignore_case,
+        non_prefix_weight: weights.non_prefix_substring,
+    };
+    let pc_am = pc(&am);
+    let ac_sm = ac(&sm);
+    let ac_ci_sm = ac(&ci_sm);
+    // the remaining transformations apply on top of case-insensitivity, if it's allowed
+    let (fold_em, fold_sm): (&dyn Matcher, &dyn Matcher) = if ignore_case {
+        (&ci_em, &ci_sm)
+    } else {
+        (&em, &sm)
+    };
+    // the untransformed matchers are tried first, so there's no point matching anything the
+    // transformations don't change
+    let pi = |m| {
+        TransformedMatcher::punctuation_insensitive(m)
+            .with_attenuation(weights.punctuation_insensitive)
+            .skip_unchanged()
+    };
+    let di = |m| {
+        TransformedMatcher::diacritic_insensitive(m)
+            .with_attenuation(weights.diacritic_insensitive)
+            .skip_unchanged()
+    };
+    let pi_em = pi(fold_em);
+    let pc_pi_em = pc(&pi_em);
+    let pi_sm = pi(fold_sm);
+    let pc_pi_sm = pc(&pi_sm);
+    let di_em = di(fold_em);
+    let pc_di_em = pc(&di_em);
+    let di_sm = di(fold_sm);
+    let pc_di_sm = pc(&di_sm);
+    let mut matchers: Vec<&dyn Matcher> = vec![&em, &pc_em, &pc_sm, &sm, &mt, &ac_sm];
+    if ignore_case {
+        matchers.extend_from_slice(&[&ci_em, &pc_ci_em, &ci_sm, &pc_ci_sm, &ci_mt, &ac_ci_sm]);
+    }
+    // Transforming paths and matching them character by character is much slower than finding
+    // substrings, so these are only tried on paths which didn't match above; they're for the
+    // paths a user's spelling doesn't quite match.
+    let fuzzy_matchers: Vec<&dyn Matcher> = vec![
+        &pc_pi_em, &pc_pi_sm, &pc_di_em, &pc_di_sm, &pc_am,
+        // weighted low enough that any of the above should win when they match
+        &pc_ss,
+    ];
+    // Typo-tolerant matchers are only consulted if nothing above matched anything, since a
+    // typo match is a much wilder guess than any real match.
+    let lm = LevenshteinMatcher {};
+    let ci_lm = ci(&lm);
+    let fold_lm: &dyn Matcher = if ignore_case { &ci_lm } else { &lm };
+    let pc_lm = pc(fold_lm);
+    let fallback_matchers: Vec<&dyn Matcher> = vec![&pc_lm];
+
+    f(&[&matchers, &fuzzy_matchers], &fallback_matchers)
+}
+
+// par_map maps f over items, spreading the work over all available cores when pazi is built with
+// the 'parallel' feature.
+#[cfg(feature = "parallel")]
+fn par_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    use rayon::prelude::*;
+    items.par_iter().map(&f).collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn par_map<T, R>(items: &[T], f: impl Fn(&T) -> R) -> Vec<R> {
+    items.iter().map(f).collect()
+}
+
+// combined_score weights how well a path matched against its frecency, and pulls the result
+// towards the top if the path was previously selected for this query. Paths which neither matched
+// nor were previously selected aren't candidates at all.
+fn combined_score(
+    best_match: Option<f64>,
+    frecency: f64,
+    learned: Option<f64>,
+    match_ratio: f64,
+) -> Option<f64> {
+    if best_match.is_none() && learned.is_none() {
+        return None;
+    }
+    let mut score = best_match.unwrap_or(0.0) * match_ratio + frecency * (1.0 - match_ratio);
+    if let Some(l) = learned {
+        score += (1.0 - score) * l;
+    }
+    Some(score)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn in_memory_db() -> PathFrecency {
+        PathFrecency {
+            db: PathDb::new(),
+            dirty: false,
+            path: PathBuf::new(),
+            exclusions: Exclusions::default(),
+            kind: PathKind::Directory,
+            match_options: MatchOptions::default(),
+            index: OnceCell::new(),
+        }
+    }
+
+    #[test]
+    fn learned_matches_favor_exact_queries() {
+        let mut f = in_memory_db();
+        f.record_selection("foobar", "/a");
+        f.record_selection("foo", "/b");
+
+        let exact = f.learned_matches("foo");
+        let prefix = f.learned_matches("fo");
+        assert!(exact["/b"] > exact["/a"]);
+        assert!(exact["/b"] > prefix["/b"]);
+        assert!(f.learned_matches("foox").is_empty());
+    }
+
+    #[test]
+    fn substring_matches_are_never_fuzzy() {
+        let mut f = in_memory_db();
+        for dir in &[
+            "/apple-pie",
+            "/aspic",
+            "/work/api",
+            "/work/API-docs",
+            "/old/api",
+        ] {
+            f.db.frecency.insert(dir.to_string());
+        }
+
+        // /apple-pie and /aspic only match 'api' as a subsequence
+        assert_eq!(
+            f.substring_matches("api"),
+            vec!["/old/api", "/work/API-docs", "/work/api"]
+        );
+        assert_eq!(f.substring_matches("API"), vec!["/work/API-docs"]);
+        assert_eq!(f.substring_matches("work api !docs"), vec!["/work/api"]);
+        assert_eq!(f.substring_matches("^api$"), vec!["/old/api", "/work/api"]);
+        assert!(f.substring_matches("apie").is_empty());
+        assert!(f.substring_matches("!api").is_empty());
+    }
+
+    #[test]
+    fn learned_matches_ignore_query_operators() {
+        let mut f = in_memory_db();
+        f.record_selection("^foo$ !bar", "/a");
+
+        assert!(f.learned_matches("foo").contains_key("/a"));
+        assert!(f.learned_matches("^foo").contains_key("/a"));
+        // with smart case, an uppercase query is a different query
+        assert!(f.learned_matches("FOO").is_empty());
+        f.record_selection("!bar", "/b");
+        assert!(f.learned_matches("!bar").is_empty());
+    }
+
+    #[test]
+    fn weights_visits_by_dwell_time() {
+        let mut f = in_memory_db();
+        f.visit("/transit".to_string());
+        f.visit("/work".to_string());
+        f.visit("/unleft".to_string());
+        f.db.entered
+            .insert("/work".to_string(), unix_now() - 60 * 60);
+        f.leave("/transit");
+        f.leave("/work");
+
+        let weight = |f: &PathFrecency, dir: &str| f.db.frecency.weight(&dir.to_string()).unwrap();
+        assert!(weight(&f, "/transit") < 1.01);
+        assert!((weight(&f, "/work") - (1.0 + MAX_DWELL_VISITS)).abs() < 0.01);
+        assert!(!f.db.entered.contains_key("/work"));
+        assert!(f.db.entered.contains_key("/unleft"));
+    }
+
+    #[test]
+    fn records_visit_stats() {
+        let mut f = in_memory_db();
+        f.db.frecency.set_max_size(2);
+        f.visit("/a".to_string());
+        f.visit("/a".to_string());
+        f.visit("/b".to_string());
+        let a = f.visit_stats("/a").unwrap();
+        assert_eq!(a.visits, 2);
+        assert!(a.last_visit > 0);
+        assert_eq!(f.visit_stats("/b").unwrap().visits, 1);
+
+        // stats go along with their path, however it's removed
+        f.remove("/b");
+        assert_eq!(f.visit_stats("/b"), None);
+        f.visit("/c".to_string());
+        // make room for /d by trimming /a
+        f.db.frecency.overwrite("/a".to_string(), 0.0);
+        f.visit("/d".to_string());
+        assert_eq!(f.visit_stats("/a"), None);
+        assert_eq!(f.db.dir_stats.len(), 2);
+    }
+
+    #[test]
+    fn finds_prunable_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let path = |name: &str| root.path().join(name).to_str().unwrap().to_string();
+        for dir in &["stale", "unknown", "weak", "kept", "pinned"] {
+            fs::create_dir(path(dir)).unwrap();
+        }
+        fs::write(path("now-a-file"), "").unwrap();
+        let mut f = in_memory_db();
+        for dir in &["gone", "now-a-file", "stale", "kept", "kept"] {
+            f.visit(path(dir));
+        }
+        f.db.frecency.insert(path("unknown"));
+        f.db.frecency.insert(path("weak"));
+        f.db.frecency.overwrite(path("weak"), 0.0);
+        f.pin(path("pinned"), None);
+        f.pin(path("pinned-gone"), None);
+        f.db.dir_stats.get_mut(&path("stale")).unwrap().last_visit -= 100 * 86400;
+
+        let prunable = |f: &PathFrecency, criteria| {
+            f.prunable(&criteria)
+                .into_iter()
+                .map(|(p, r)| (p.strip_prefix(&path("")).unwrap().to_string(), r))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            prunable(&f, prune::Criteria::default()),
+            vec![
+                ("gone".to_string(), prune::Reason::Missing),
+                (
+                    "now-a-file".to_string(),
+                    prune::Reason::WrongKind("directory".to_string())
+                ),
+            ]
+        );
+        let criteria = prune::Criteria {
+            older_than: Some(Duration::from_secs(90 * 86400)),
+            below_weight: Some(0.5),
+            ..Default::default()
+        };
+        let pruned: Vec<_> = prunable(&f, criteria).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(pruned, vec!["gone", "now-a-file", "stale", "weak"]);
+    }
+
+    #[test]
+    fn unpins_by_name_or_dir() {
+        let mut f = in_memory_db();
+        f.pin("/a".to_string(), Some("a"));
+        f.pin("/b".to_string(), None);
+        assert_eq!(f.pinned_dir_named("a"), Some("/a"));
+        assert_eq!(f.pins()["/a"], Some("a".to_string()));
+        assert_eq!(f.pins()["/b"], None);
+
+        assert_eq!(f.unpin("a"), Some("/a".to_string()));
+        assert_eq!(f.pinned_dir_named("a"), None);
+        assert_eq!(f.unpin("/b"), Some("/b".to_string()));
+        assert_eq!(f.unpin("/b"), None);
+        assert!(f.pins().is_empty());
+    }
+
+    #[test]
+    fn explains_directory_matches() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("project");
+        fs::create_dir(&project).unwrap();
+        let project = project.to_str().unwrap().to_string();
+        let gone = root
+            .path()
+            .join("project-old")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut f = in_memory_db();
+        f.visit(project.clone());
+        f.visit(gone.clone());
+        f.visit(gone.clone());
+
+        let explanations = f.explain("proj");
+        assert_eq!(explanations.len(), 2);
+        assert_eq!(explanations[0].path, gone);
+        assert!(!explanations[0].exists);
+        let e = &explanations[1];
+        assert!(e.exists && !e.fallback && e.learned.is_none());
+        assert!(e
+            .matches
+            .iter()
+            .any(|m| m.name == "path component(substring)"));
+
+        let matches: Vec<_> = f.directory_matches("proj").collect();
+        assert_eq!(matches, vec![(project, e.score)]);
+    }
+
+    #[test]
+    fn only_trims_missing_paths_when_asked() {
+        let root = tempfile::tempdir().unwrap();
+        let here = root.path().to_str().unwrap().to_string();
+        let gone = root.path().join("gone").to_str().unwrap().to_string();
+        let mut f = in_memory_db();
+        f.visit(here.clone());
+        f.visit(gone.clone());
+        f.dirty = false;
+
+        let paths = |iter: FrecentPathIter| iter.map(|(p, _)| p).collect::<Vec<_>>();
+        let mut kept = paths(f.items_with_frecency().missing(Missing::Keep));
+        kept.sort();
+        assert_eq!(kept, vec![here.clone(), gone.clone()]);
+        let skipped = paths(f.items_with_frecency().missing(Missing::Skip));
+        assert_eq!(skipped, vec![here.clone()]);
+        assert!(f.contains(&gone) && !f.dirty);
+
+        let trimmed = paths(f.items_with_frecency());
+        assert_eq!(trimmed, vec![here]);
+        assert!(!f.contains(&gone) && f.dirty);
+    }
+
+    #[test]
+    fn smart_case_matches_uppercase_queries_case_sensitively() {
+        let root = tempfile::tempdir().unwrap();
+        let docs = root.path().join("Docs");
+        let docs_old = root.path().join("docs-old");
+        fs::create_dir(&docs).unwrap();
+        fs::create_dir(&docs_old).unwrap();
+        let (docs, docs_old) = (docs.to_str().unwrap(), docs_old.to_str().unwrap());
+        let mut f = in_memory_db();
+        f.visit(docs.to_string());
+        f.visit(docs_old.to_string());
+
+        let matches: Vec<_> = f.directory_matches("Docs").map(|(p, _)| p).collect();
+        assert_eq!(matches, vec![docs]);
+        let matches: Vec<_> = f.directory_matches("docs").map(|(p, _)| p).collect();
+        assert_eq!(matches.len(), 2);
+
+        f.set_match_options(MatchOptions {
+            smart_case: false,
+            ..Default::default()
+        });
+        let matches: Vec<_> = f.directory_matches("Docs").map(|(p, _)| p).collect();
+        assert_eq!(matches, vec![docs, docs_old]);
+    }
+
+    #[test]
+    fn pins_outrank_better_matches() {
+        let root = tempfile::tempdir().unwrap();
+        let infra = root.path().join("infra");
+        let prod_config = infra.join("prod-config");
+        fs::create_dir_all(&prod_config).unwrap();
+        let (infra, prod_config) = (infra.to_str().unwrap(), prod_config.to_str().unwrap());
+        let mut f = in_memory_db();
+        for _ in 0..5 {
+            f.visit(infra.to_string());
+        }
+        f.pin(prod_config.to_string(), None);
+
+        // 'infra' is the final component of the unpinned directory, so it's the better match
+        let best_match = |path: &str| {
+            f.explain("infra")
+                .into_iter()
+                .find(|e| e.path == path)
+                .and_then(|e| e.matches.iter().map(|m| m.score).reduce(f64::max))
+                .unwrap()
+        };
+        assert!(best_match(infra) > best_match(prod_config));
+        let matches: Vec<_> = f.directory_matches("infra").map(|(p, _)| p).collect();
+        assert_eq!(matches, vec![prod_config, infra]);
+    }
+
+    #[test]
+    fn typo_matches_never_outrank_real_matches() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = |name: &str| {
+            let path = root.path().join(name);
+            fs::create_dir(&path).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let kubernetes = dir("kubernetes");
+        let notes = dir("kuberentes-notes");
+        let mut f = in_memory_db();
+        for _ in 0..10 {
+            f.visit(kubernetes.clone());
+        }
+        f.visit(notes.clone());
+
+        // a real match keeps the more frecent typo match out entirely
+        let matches: Vec<_> = f.directory_matches("kuberentes").map(|(p, _)| p).collect();
+        assert_eq!(matches, vec![notes]);
+
+        // typos are only tolerated when nothing else matches
+        let matches: Vec<_> = f.directory_matches("kubernetez").map(|(p, _)| p).collect();
+        assert_eq!(matches, vec![kubernetes]);
+        assert_eq!(f.directory_matches("xyzzy").count(), 0);
+    }
+}
@@ -1,28 +1,71 @@
+#![cfg_attr(feature = "nightly", feature(test))]
+
 #[macro_use]
 mod pazi_result;
 
+#[cfg(all(feature = "nightly", test))]
+mod bench;
+
+mod config;
+mod doctor;
 mod edit;
+mod exclusions;
 mod frecency;
 mod frecent_paths;
 mod importers;
 mod interactive;
 mod matcher;
+mod path_query;
 mod pipe;
+mod prune;
+mod query;
 mod shells;
+mod view;
 
+use std::cmp::Ordering;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
 use crossbeam_channel as channel;
 use log::debug;
+use serde::Serialize;
 
-use frecent_paths::{FrecentPathIter, PathFrecency};
+use config::Config;
+use exclusions::Exclusions;
+use frecent_paths::{FrecentPathIter, Missing, PathFrecency, PathFrecencyDiff, PathKind};
+use matcher::{GlobMatcher, Matcher, RegexMatcher};
 use pazi_result::*;
 use shells::SUPPORTED_SHELLS;
 
-const PAZI_DB_NAME: &str = "pazi_dirs.msgpack";
+const PAZI_IGNORE_NAME: &str = "ignore";
+
+const CONFIG_HELP: &str = "\
+SETTINGS:
+    database.path                  where the database is stored
+    database.max_size              how many directories, and how many files, are remembered
+    database.half_life_days        how long it takes for a visit to count for half as much
+    exclusions                     globs of paths which are never recorded
+    jump.pipe                      a program to pick between matches with, for 'z -i'
+    matching.smart_case            match queries with uppercase letters case-sensitively
+    matching.weights.*             how matches are ranked; see 'pazi config list'
+
+    Each setting may be overridden by an environment variable named after it, e.g.
+    PAZI_DATABASE_MAX_SIZE for database.max_size. Lists are separated by ':' in the environment.";
+
+const QUERY_SYNTAX_HELP: &str = "\
+QUERY SYNTAX:
+    A query is one or more terms matched against directories in order. Each term may use the
+    following operators:
+
+    ^foo    foo must be at the start of a path component
+    foo$    foo must be at the end of the final path component
+    foo/    foo must be in the final path component
+    !foo    exclude directories containing foo; -foo is equivalent
+
+    Operators may be combined, e.g. '^foo$' requires the final component to be exactly foo.";
 
 fn main() {
     let res = _main();
@@ -40,12 +83,27 @@ fn main() {
 // This should be replaced by a normal enum + const "as_str" for each variant once rust stable
 // supports const functions.
 macro_rules! SUBCOMMAND {
+    (Add) => {
+        "add"
+    };
     (Complete) => {
         "complete"
     };
+    (Config) => {
+        "config"
+    };
+    (Doctor) => {
+        "doctor"
+    };
     (Edit) => {
         "edit"
     };
+    (Explain) => {
+        "explain"
+    };
+    (File) => {
+        "file"
+    };
     (Import) => {
         "import"
     };
@@ -55,6 +113,21 @@ macro_rules! SUBCOMMAND {
     (Jump) => {
         "jump"
     };
+    (Pin) => {
+        "pin"
+    };
+    (Prune) => {
+        "prune"
+    };
+    (Query) => {
+        "query"
+    };
+    (Remove) => {
+        "remove"
+    };
+    (Unpin) => {
+        "unpin"
+    };
     (View) => {
         "view"
     };
@@ -74,6 +147,27 @@ fn _main() -> PaziResult {
                 .long("debug")
                 .env("PAZI_DEBUG"),
         )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Add))
+                .about("Add directories to the frecency database")
+                .usage("pazi add [--score <visits>] <directory>...")
+                .arg(
+                    Arg::with_name("score")
+                        .help(
+                            "record the directories as visited this many times just now, \
+                             replacing any existing score; by default, directories already in \
+                             the database are left as they are, and others count as visited once",
+                        )
+                        .takes_value(true)
+                        .long("score"),
+                )
+                .arg(
+                    Arg::with_name("dir_target")
+                        .help("the directories to add; globs are expanded")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
         .subcommand(
             // used by the shell completion functions internally, it shouldn't be called directly
             SUPPORTED_SHELLS.iter().fold(
@@ -86,20 +180,88 @@ fn _main() -> PaziResult {
                         SubCommand::with_name(sh)
                             .setting(AppSettings::Hidden)
                             .setting(AppSettings::DisableHelpSubcommand)
-                            .arg(Arg::with_name("dir_target")),
+                            .setting(AppSettings::AllowLeadingHyphen)
+                            .args(&pattern_args())
+                            .arg(Arg::with_name("dir_target").multiple(true)),
                     )
                 },
             ),
         )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Config))
+                .setting(AppSettings::DisableHelpSubcommand)
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .about("Get or set configuration")
+                .after_help(CONFIG_HELP)
+                .subcommand(
+                    SubCommand::with_name("get")
+                        .about("Print the value of a setting")
+                        .arg(Arg::with_name("key").required(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Set a setting in the config file")
+                        .setting(AppSettings::AllowLeadingHyphen)
+                        .arg(Arg::with_name("key").required(true))
+                        .arg(
+                            Arg::with_name("value")
+                                .help("the value; settings which are lists take several")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(SubCommand::with_name("list").about("Print every setting which is set"))
+                .subcommand(SubCommand::with_name("path").about("Print the config file's path")),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Doctor))
+                .about("Check that pazi is set up correctly and recording visits")
+                .usage("pazi doctor"),
+        )
         .subcommand(
             SubCommand::with_name(SUBCOMMAND!(Edit))
                 .about("Edit the frecency database")
-                .usage("pazi edit [<filter>]")
+                .usage("pazi edit [--regex | --glob] [<filter>]")
+                .args(&pattern_args())
                 .arg(
                     Arg::with_name("filter")
                         .help("filter matches down further and edit that subset"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Explain))
+                .setting(AppSettings::DisableHelpSubcommand)
+                .setting(AppSettings::AllowLeadingHyphen)
+                .about("Explain how the directories matching a query were ranked")
+                .usage("pazi explain [--count <n>] <query>...")
+                .arg(
+                    Arg::with_name("count")
+                        .help("how many of the top candidates to explain")
+                        .takes_value(true)
+                        .default_value("5")
+                        .long("count")
+                        .short("n"),
+                )
+                .arg(
+                    Arg::with_name("dir_target")
+                        .help("the query to explain, as it would be given to 'z'")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(File))
+                .setting(AppSettings::DisableHelpSubcommand)
+                .about("Print the best matching frecent file")
+                .usage("pazi file [--list] [<filter>]")
+                .arg(
+                    Arg::with_name("list")
+                        .help("print every matching file along with its score")
+                        .long("list")
+                        .short("l"),
+                )
+                .arg(Arg::with_name("file_target").help("the query to match files against")),
+        )
         .subcommand(
             SubCommand::with_name(SUBCOMMAND!(Init))
                 .about("Prints initialization logic for the given shell to eval")
@@ -123,6 +285,9 @@ fn _main() -> PaziResult {
                 .setting(AppSettings::Hidden)
                 .setting(AppSettings::DisableHelpSubcommand)
                 .about("Select a directory to jump to")
+                // allows '-foo' exclusions in queries
+                .setting(AppSettings::AllowLeadingHyphen)
+                .after_help(QUERY_SYNTAX_HELP)
                 .arg(
                     Arg::with_name("interactive")
                         .help("interactively search directory matches")
@@ -135,13 +300,216 @@ fn _main() -> PaziResult {
                         .takes_value(true)
                         .long("pipe"),
                 )
-                .arg(Arg::with_name("dir_target")),
+                .args(&pattern_args())
+                .arg(Arg::with_name("dir_target").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Pin))
+                .about("Pin a directory so it always ranks first when it matches")
+                .usage("pazi pin <directory> [--name <name>]")
+                .arg(Arg::with_name("dir_target").help("the directory to pin"))
+                .arg(
+                    Arg::with_name("name")
+                        .help("a name which will jump directly to this directory, e.g. 'z <name>'")
+                        .takes_value(true)
+                        .long("name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Prune))
+                .about("Remove directories which no longer exist from the frecency database")
+                .usage(
+                    "pazi prune [--dry-run] [--excluded] [--older-than <age>] \
+                     [--below-score <visits>]",
+                )
+                .after_help(
+                    "Directories which no longer exist, or are no longer directories, are always \
+                     removed, unless they're pinned. The other options remove more.",
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("print what would be removed without removing it")
+                        .long("dry-run")
+                        .short("n"),
+                )
+                .arg(
+                    Arg::with_name("excluded")
+                        .help(
+                            "remove entries matching a glob in the ignore file or within a \
+                             directory containing a '.pazi-ignore' file",
+                        )
+                        .long("excluded"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help(
+                            "remove directories last visited longer ago than this, e.g. 90d; \
+                             directories whose last visit isn't known are kept",
+                        )
+                        .takes_value(true)
+                        .long("older-than"),
+                )
+                .arg(
+                    Arg::with_name("below-score")
+                        .help(
+                            "remove directories whose score is below this many visits, as for \
+                             'pazi add --score'",
+                        )
+                        .takes_value(true)
+                        .long("below-score"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Query))
+                .setting(AppSettings::DisableHelpSubcommand)
+                // allows '-foo' exclusions in queries
+                .setting(AppSettings::AllowLeadingHyphen)
+                .about("Print the directories matching a query, best first")
+                .usage(
+                    "pazi query [--limit <n>] [--score] [--json | --null] [--exclude-cwd] \
+                     [--exists-only] [--regex | --glob] [<query>...]",
+                )
+                .after_help(QUERY_SYNTAX_HELP)
+                .arg(
+                    Arg::with_name("limit")
+                        .help("print at most this many directories")
+                        .takes_value(true)
+                        .long("limit")
+                        .short("n"),
+                )
+                .arg(
+                    Arg::with_name("score")
+                        .help("print each directory's score before it, separated by a tab")
+                        .long("score")
+                        .short("s"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("print a JSON array of objects with 'path' and 'score' keys")
+                        .long("json")
+                        .conflicts_with("null"),
+                )
+                .arg(
+                    Arg::with_name("null")
+                        .help("terminate each directory with a NUL rather than a newline")
+                        .long("null")
+                        .short("0"),
+                )
+                .arg(
+                    Arg::with_name("exclude-cwd")
+                        .help("leave out the current directory")
+                        .long("exclude-cwd"),
+                )
+                .arg(
+                    Arg::with_name("exists-only")
+                        .help("leave out directories which no longer exist")
+                        .long("exists-only"),
+                )
+                .args(&pattern_args())
+                .arg(
+                    Arg::with_name("dir_target")
+                        .help("the query, as it would be given to 'z'; without one, every directory is printed")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Remove))
+                .about("Remove directories from the frecency database")
+                .usage(
+                    "pazi remove [--dry-run] [--yes] <directory>...\n    \
+                     pazi remove [--dry-run] [--yes] [--regex | --glob] --match <query>",
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("print what would be removed without removing it")
+                        .long("dry-run")
+                        .short("n"),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .help("remove several entries selected by a glob or --match")
+                        .long("yes")
+                        .short("y"),
+                )
+                .arg(
+                    Arg::with_name("match")
+                        .help("remove every directory containing each term of a query")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .long("match")
+                        .short("m"),
+                )
+                .args(&pattern_args())
+                .arg(
+                    Arg::with_name("dir_target")
+                        .help("the directories to remove; globs are matched against the database")
+                        .multiple(true)
+                        .conflicts_with("match")
+                        .required_unless("match"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(SUBCOMMAND!(Unpin))
+                .about("Unpin a previously pinned directory")
+                .usage("pazi unpin <directory | name>")
+                .arg(
+                    Arg::with_name("target")
+                        .help("the directory or name of the pinned directory to unpin"),
+                ),
         )
         .subcommand(
             SubCommand::with_name(SUBCOMMAND!(View))
                 .setting(AppSettings::DisableHelpSubcommand)
                 .about("View the frecency database")
-                .arg(Arg::with_name("dir_target").help("filter matches down further")),
+                .setting(AppSettings::AllowLeadingHyphen)
+                .after_help(QUERY_SYNTAX_HELP)
+                .arg(
+                    Arg::with_name("format")
+                        .help(
+                            "how to print the database; defaults to a table when printing to a \
+                             terminal, and tsv otherwise",
+                        )
+                        .takes_value(true)
+                        .possible_values(view::FORMATS)
+                        .long("format"),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .help("what to order directories by")
+                        .takes_value(true)
+                        .possible_values(view::SORTS)
+                        .default_value("score")
+                        .long("sort"),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .help("print at most this many directories")
+                        .takes_value(true)
+                        .long("limit")
+                        .short("n"),
+                )
+                .arg(
+                    Arg::with_name("fields")
+                        .help(
+                            "the comma separated columns to print as tsv; defaults to the score \
+                             and path",
+                        )
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .possible_values(view::FIELDS)
+                        .long("fields"),
+                )
+                .arg(
+                    Arg::with_name("raw")
+                        .help("print scores as stored, rather than normalized out of 100")
+                        .long("raw"),
+                )
+                .args(&pattern_args())
+                .arg(
+                    Arg::with_name("dir_target")
+                        .help("filter matches down further")
+                        .multiple(true),
+                ),
         )
         .subcommand(
             SubCommand::with_name(SUBCOMMAND!(Visit))
@@ -149,6 +517,18 @@ fn _main() -> PaziResult {
                 .setting(AppSettings::Hidden)
                 .setting(AppSettings::DisableHelpSubcommand)
                 .about("Add or visit a directory in the frecency database")
+                .arg(
+                    Arg::with_name("file")
+                        .help("visit a file rather than a directory")
+                        .long("file"),
+                )
+                .arg(
+                    Arg::with_name("leaving")
+                        .help("the directory being left, so time spent in it may be recorded")
+                        .takes_value(true)
+                        .long("leaving")
+                        .conflicts_with("file"),
+                )
                 .arg(Arg::with_name("dir_target")),
         )
         // Code after this comment is deprecated in favor of .PaziSubcommand::Jump, but is left in
@@ -207,27 +587,82 @@ fn _main() -> PaziResult {
         }
     }
 
+    let config_dir = match config_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+    let config = Config::load(&config_dir);
+    // None of these need a valid config; 'config' is how to fix an invalid one, and 'doctor'
+    // reports it
+    match flags.subcommand() {
+        (SUBCOMMAND!(Config), Some(cmd)) => {
+            return handle_config(cmd, &config_dir, config);
+        }
+        (SUBCOMMAND!(Doctor), Some(_)) => {
+            return if doctor::run(&config_dir, &config) {
+                PaziResult::Success
+            } else {
+                PaziResult::Error
+            };
+        }
+        (SUBCOMMAND!(Init), Some(init)) => {
+            return handle_init(init);
+        }
+        _ => {}
+    }
+    let config = match config {
+        Ok(c) => c,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
     match flags.subcommand() {
+        (SUBCOMMAND!(Add), Some(add)) => {
+            return handle_add(add, &config);
+        }
         (SUBCOMMAND!(Complete), Some(completion)) => {
-            return handle_completion(completion);
+            return handle_completion(completion, &config);
         }
         (SUBCOMMAND!(Edit), Some(edit)) => {
-            return handle_edit(edit);
+            return handle_edit(edit, &config);
         }
-        (SUBCOMMAND!(Import), Some(import)) => {
-            return handle_import(import);
+        (SUBCOMMAND!(Explain), Some(explain)) => {
+            return handle_explain(explain, &config);
         }
-        (SUBCOMMAND!(Init), Some(init)) => {
-            return handle_init(init);
+        (SUBCOMMAND!(File), Some(file)) => {
+            return handle_file(file, &config);
+        }
+        (SUBCOMMAND!(Import), Some(import)) => {
+            return handle_import(import, &config);
         }
         (SUBCOMMAND!(Jump), Some(jump)) => {
-            return handle_jump(jump);
+            return handle_jump(jump, &config);
+        }
+        (SUBCOMMAND!(Pin), Some(pin)) => {
+            return handle_pin(pin, &config);
+        }
+        (SUBCOMMAND!(Prune), Some(prune)) => {
+            return handle_prune(prune, &config);
+        }
+        (SUBCOMMAND!(Query), Some(query)) => {
+            return handle_query(query, &config);
+        }
+        (SUBCOMMAND!(Remove), Some(remove)) => {
+            return handle_remove(remove, &config);
+        }
+        (SUBCOMMAND!(Unpin), Some(unpin)) => {
+            return handle_unpin(unpin, &config);
         }
         (SUBCOMMAND!(View), Some(view)) => {
-            return handle_print_frecency(view);
+            return handle_print_frecency(view, &config);
         }
         (SUBCOMMAND!(Visit), Some(visit)) => {
-            return handle_visit(visit);
+            return handle_visit(visit, &config);
         }
         unknown => debug!(
             "unrecognized subcommand: not an error for backwards compatibility: {:?}",
@@ -237,8 +672,7 @@ fn _main() -> PaziResult {
 
     // the remainder of this fn is backwards compatibility code, all of this should vanish before
     // 1.0
-    let path = frecency_path().expect("could not get frecency db path");
-    let mut frecency = match PathFrecency::load(&path) {
+    let mut frecency = match load_frecency(&config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
@@ -321,14 +755,164 @@ fn _main() -> PaziResult {
     res
 }
 
-fn load_frecency() -> Result<PathFrecency> {
-    let path = frecency_path().expect("could not get frecency db path");
+// pattern_args are the flags which select matching the query as a pattern rather than fuzzily
+fn pattern_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("regex")
+            .help("match the query as a regular expression against the full path")
+            .long("regex")
+            .short("r")
+            .conflicts_with("glob"),
+        Arg::with_name("glob")
+            .help("match the query as a glob against the full path")
+            .long("glob")
+            .short("g"),
+    ]
+}
+
+// query_pattern compiles the query into a pattern matcher if one of the 'pattern_args' was given
+fn query_pattern(
+    cmd: &ArgMatches,
+    query: Option<&str>,
+) -> Result<Option<Box<dyn Matcher>>, String> {
+    let regex = cmd.is_present("regex");
+    if !regex && !cmd.is_present("glob") {
+        return Ok(None);
+    }
+    let kind = if regex { "regex" } else { "glob" };
+    let pattern = query.ok_or_else(|| format!("--{} requires a pattern", kind))?;
+    let invalid = |e: &dyn std::fmt::Display| format!("invalid {} '{}': {}", kind, pattern, e);
+    let matcher: Box<dyn Matcher> = if regex {
+        Box::new(RegexMatcher::new(pattern).map_err(|e| invalid(&e))?)
+    } else {
+        Box::new(GlobMatcher::new(pattern).map_err(|e| invalid(&e))?)
+    };
+    Ok(Some(matcher))
+}
+
+fn load_frecency(config: &Config) -> Result<PathFrecency> {
+    let ignore_path = config_dir()
+        .expect("could not get config path")
+        .join(PAZI_IGNORE_NAME);
+    let mut exclusions =
+        Exclusions::new(&config.exclusions).context("invalid exclusions in config")?;
+    exclusions.extend(Exclusions::load(&ignore_path)?);
+
+    let mut frecency = PathFrecency::load(config.db_path())?;
+    frecency.set_limits(config.database.max_size, config.database.half_life());
+    frecency.set_exclusions(exclusions);
+    frecency.set_match_options(config.matching.clone());
+    Ok(frecency)
+}
+
+fn load_file_frecency(config: &Config) -> Result<PathFrecency> {
+    let mut frecency = load_frecency(config)?;
+    frecency.set_kind(PathKind::File);
+    Ok(frecency)
+}
+
+fn handle_add(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let visits = match cmd.value_of("score").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(visits)) if visits > 0.0 && visits.is_finite() => Some(visits),
+        Some(_) => {
+            println!("add: --score must be a positive number of visits");
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    // Every directory is checked before anything is added, so a typo doesn't leave the database
+    // half provisioned
+    let mut dirs = Vec::new();
+    for target in cmd.values_of("dir_target").into_iter().flatten() {
+        let paths = match expand_glob(target) {
+            Ok(paths) => paths,
+            Err(e) => {
+                println!("add: {}", e);
+                return PaziResult::Error;
+            }
+        };
+        for path in paths {
+            let dir = match std::fs::canonicalize(&path) {
+                Ok(dir) if dir.is_dir() => dir,
+                Ok(_) => {
+                    println!("add: not a directory: {}", path.display());
+                    return PaziResult::Error;
+                }
+                Err(e) => {
+                    println!("add: could not resolve {}: {}", path.display(), e);
+                    return PaziResult::Error;
+                }
+            };
+            if frecency.is_excluded(&dir) {
+                println!("add: {} is excluded", dir.display());
+                return PaziResult::Error;
+            }
+            match dir.to_str() {
+                Some(dir) => dirs.push(dir.to_string()),
+                None => {
+                    println!("add: directory is not valid utf-8: {:?}", dir);
+                    return PaziResult::Error;
+                }
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+
+    let score = frecency.score_for_visits(visits.unwrap_or(1.0));
+    let additions: Vec<_> = dirs
+        .into_iter()
+        .filter(|dir| visits.is_some() || !frecency.contains(dir))
+        .map(|dir| (dir, score))
+        .collect();
+    let added: Vec<_> = additions.iter().map(|(dir, _)| dir.clone()).collect();
+    if let Err(e) = frecency.apply_diff(PathFrecencyDiff::new(additions, Vec::new())) {
+        println!("pazi: error adding directories: {:?}", e);
+        return PaziResult::Error;
+    }
+    if let Err(e) = frecency.save_to_disk() {
+        println!("pazi: error saving db: {:?}", e);
+        return PaziResult::Error;
+    }
+    for dir in added {
+        println!("added {}", dir);
+    }
+    PaziResult::Success
+}
+
+// is_glob returns whether an argument contains glob metacharacters
+fn is_glob(arg: &str) -> bool {
+    arg.contains(['*', '?', '['])
+}
 
-    PathFrecency::load(&path)
+// expand_glob expands a glob against the filesystem, as a shell would, to the directories it
+// matches. Anything else is returned as is.
+fn expand_glob(target: &str) -> Result<Vec<PathBuf>, String> {
+    if !is_glob(target) {
+        return Ok(vec![PathBuf::from(target)]);
+    }
+    let paths: Vec<_> = glob::glob(target)
+        .map_err(|e| format!("invalid glob '{}': {}", target, e))?
+        .filter_map(Result::ok)
+        .filter(|path| path.is_dir())
+        .collect();
+    if paths.is_empty() {
+        return Err(format!("no directories match {}", target));
+    }
+    Ok(paths)
 }
 
-fn handle_completion(cmd: &ArgMatches) -> PaziResult {
-    let mut frecency = match load_frecency() {
+fn handle_completion(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let mut frecency = match load_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
@@ -338,7 +922,19 @@ fn handle_completion(cmd: &ArgMatches) -> PaziResult {
 
     match cmd.subcommand() {
         ("zsh", Some(sub_cmd)) => {
-            handle_zsh_completion(find_matches(&mut frecency, sub_cmd.value_of("dir_target")))
+            let query = query_terms(sub_cmd);
+            let pattern = match query_pattern(sub_cmd, query.as_deref()) {
+                Ok(p) => p,
+                Err(e) => {
+                    println!("pazi: {}", e);
+                    return PaziResult::Error;
+                }
+            };
+            handle_zsh_completion(find_matches(
+                &mut frecency,
+                query.as_deref(),
+                pattern.as_deref(),
+            ))
         }
         ("bash", Some(_cmd)) => println!("bash not supported yet"),
         ("fish", Some(_cmd)) => println!("fish not supported yet"),
@@ -347,13 +943,22 @@ fn handle_completion(cmd: &ArgMatches) -> PaziResult {
     PaziResult::Success
 }
 
+// query_terms returns the query given in 'dir_target'. A query may be made up of several terms,
+// which are joined by spaces.
+fn query_terms(cmd: &ArgMatches) -> Option<String> {
+    cmd.values_of("dir_target")
+        .map(|terms| terms.collect::<Vec<_>>().join(" "))
+}
+
 pub fn find_matches<'a>(
     frecency: &'a mut PathFrecency,
     dir_target: Option<&str>,
+    pattern: Option<&dyn Matcher>,
 ) -> FrecentPathIter<'a> {
-    match dir_target {
-        Some(to) => frecency.directory_matches(to),
-        None => frecency.items_with_frecency(),
+    match (pattern, dir_target) {
+        (Some(p), _) => frecency.pattern_matches(p),
+        (None, Some(to)) => frecency.directory_matches(to),
+        (None, None) => frecency.items_with_frecency(),
     }
 }
 
@@ -364,8 +969,8 @@ pub fn handle_zsh_completion(matching_dirs: FrecentPathIter) {
     }
 }
 
-fn handle_edit(cmd: &ArgMatches) -> PaziResult {
-    let mut frecency = match load_frecency() {
+fn handle_edit(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let mut frecency = match load_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
@@ -373,13 +978,23 @@ fn handle_edit(cmd: &ArgMatches) -> PaziResult {
         }
     };
 
+    let pattern = match query_pattern(cmd, cmd.value_of("filter")) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+
     let mut fclone = frecency.clone();
-    let matches = match cmd.value_of("filter") {
-        Some(filter) => fclone.directory_matches_raw(filter),
-        None => fclone.items_with_frecency_raw(),
+    let pins = frecency.pins();
+    let matches = match (pattern, cmd.value_of("filter")) {
+        (Some(p), _) => fclone.pattern_matches_raw(p.as_ref()),
+        (None, Some(filter)) => fclone.directory_matches_raw(filter),
+        (None, None) => fclone.items_with_frecency_raw(),
     };
     let match_vec: Vec<_> = matches.collect();
-    let diff = match edit::edit(&match_vec) {
+    let diff = match edit::edit(&match_vec, &pins) {
         Ok(d) => d,
         Err(e) => {
             println!("Error editing: {:?}", e);
@@ -399,69 +1014,172 @@ fn handle_edit(cmd: &ArgMatches) -> PaziResult {
     }
 }
 
-fn handle_init(cmd: &ArgMatches) -> PaziResult {
-    match cmd.value_of("shell") {
-        Some(s) => match shells::from_name(s) {
-            Some(s) => {
-                println!("{}", s.pazi_init());
-                PaziResult::Success
-            }
-            None => {
-                println!("{}\n\nUnsupported shell: {}", cmd.usage(), s);
-                PaziResult::Error
-            }
-        },
-        None => {
-            println!("{}\n\ninit requires an argument", cmd.usage());
-            PaziResult::Error
-        }
-    }
-}
-
-fn handle_import(cmd: &ArgMatches) -> PaziResult {
-    let mut frecency = match load_frecency() {
+fn handle_explain(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let frecency = match load_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
             return PaziResult::Error;
         }
     };
-
-    let stats = match cmd.value_of("autojumper") {
-        Some("fasd") => match importers::Fasd::import(&mut frecency) {
-            Ok(stats) => stats,
-            Err(e) => {
-                println!("error importing: {:?}", e);
-                return PaziResult::Error;
-            }
-        },
-        Some(s) => {
-            println!("{}\n\nUnsupported import target: {}", cmd.usage(), s);
-            return PaziResult::Error;
-        }
-        None => {
-            println!("{}\n\nimport requires an argument", cmd.usage());
+    let count: usize = match cmd.value_of("count").unwrap().parse() {
+        Ok(c) => c,
+        Err(e) => {
+            println!("explain: invalid count: {}", e);
             return PaziResult::Error;
         }
     };
+    let query = query_terms(cmd).unwrap();
 
-    match frecency.save_to_disk() {
-        Ok(_) => {
-            println!(
-                "imported {} items from fasd (out of {} in its db)",
-                stats.items_visited, stats.items_considered
-            );
-            PaziResult::Success
+    if let Some(dir) = frecency.pinned_dir_named(&query) {
+        if Path::new(dir).is_dir() {
+            println!("'{}' is the name of a pinned directory, {}", query, dir);
+            println!("it is jumped to directly, without any matching");
+            return PaziResult::Success;
         }
-        Err(e) => {
-            println!("pazi: error adding directory: {:?}", e);
+        println!(
+            "'{}' is the name of a pinned directory, {}, but it does not exist\n",
+            query, dir
+        );
+    }
+
+    let explanations = frecency.explain(&query);
+    if explanations.is_empty() {
+        println!("no directories match '{}'", query);
+        return PaziResult::Error;
+    }
+    if explanations[0].fallback {
+        println!(
+            "nothing matched '{}', so typo-tolerant matching was used\n",
+            query
+        );
+    }
+
+    let mut rank = 0;
+    for mut e in explanations {
+        if rank == count {
+            break;
+        }
+        if !e.exists {
+            println!("-  {}", e.path);
+            if e.pinned {
+                // pins are kept while they're missing, e.g. on an unmounted drive
+                println!("   missing: the directory is pinned, but doesn't exist right now\n");
+            } else {
+                println!("   trimmed: the directory no longer exists\n");
+            }
+            continue;
+        }
+        rank += 1;
+        println!("{}. {}", rank, e.path);
+        println!("   score:     {:.5}", e.score);
+        println!("   frecency:  {:.5} (normalized)", e.frecency);
+        if e.pinned {
+            println!("   pinned:    ranked before unpinned directories");
+        }
+        if let Some(learned) = e.learned {
+            println!(
+                "   learned:   {:.5} (previously selected for this query)",
+                learned
+            );
+        }
+        if e.matches.is_empty() {
+            println!("   no matchers matched");
+        }
+        // best match first, since that's the one which counts
+        e.matches
+            .sort_by(|lhs, rhs| rhs.score.partial_cmp(&lhs.score).unwrap_or(Ordering::Equal));
+        for m in e.matches {
+            println!(
+                "   matcher:   {:.5} raw, {:.5} attenuated\t{}",
+                m.raw, m.score, m.name
+            );
+        }
+        println!();
+    }
+    PaziResult::Success
+}
+
+fn handle_config(cmd: &ArgMatches, config_dir: &Path, config: Result<Config>) -> PaziResult {
+    let (subcommand, args) = match cmd.subcommand() {
+        (subcommand, Some(args)) => (subcommand, args),
+        _ => unreachable!("a config subcommand is required"),
+    };
+    if subcommand == "path" {
+        println!("{}", config_dir.join(config::CONFIG_NAME).display());
+        return PaziResult::Success;
+    }
+    if subcommand == "set" {
+        let key = args.value_of("key").unwrap();
+        let values: Vec<_> = args.values_of("value").unwrap().collect();
+        return match config::set(config_dir, key, &values) {
+            Ok(()) => PaziResult::Success,
+            Err(e) => {
+                println!("pazi: {:#}", e);
+                PaziResult::Error
+            }
+        };
+    }
+
+    let config = match config {
+        Ok(c) => c,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+    match subcommand {
+        "get" => match config.get(args.value_of("key").unwrap()) {
+            Ok(Some(toml::Value::String(s))) => println!("{}", s),
+            Ok(Some(toml::Value::Array(values))) => {
+                for value in values {
+                    match value {
+                        toml::Value::String(s) => println!("{}", s),
+                        v => println!("{}", v),
+                    }
+                }
+            }
+            Ok(Some(value)) => println!("{}", value),
+            // unset, like 'git config'
+            Ok(None) => return PaziResult::Error,
+            Err(e) => {
+                println!("pazi: {:#}", e);
+                return PaziResult::Error;
+            }
+        },
+        "list" => {
+            for key in config::KEYS {
+                if let Ok(Some(value)) = config.get(key) {
+                    println!("{} = {}", key, value);
+                }
+            }
+        }
+        _ => unreachable!("unknown config subcommand"),
+    }
+    PaziResult::Success
+}
+
+fn handle_init(cmd: &ArgMatches) -> PaziResult {
+    match cmd.value_of("shell") {
+        Some(s) => match shells::from_name(s) {
+            Some(s) => {
+                println!("{}", s.pazi_init());
+                PaziResult::Success
+            }
+            None => {
+                println!("{}\n\nUnsupported shell: {}", cmd.usage(), s);
+                PaziResult::Error
+            }
+        },
+        None => {
+            println!("{}\n\ninit requires an argument", cmd.usage());
             PaziResult::Error
         }
     }
 }
 
-fn handle_jump(cmd: &ArgMatches) -> PaziResult {
-    let mut frecency = match load_frecency() {
+fn handle_import(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let mut frecency = match load_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
@@ -469,8 +1187,114 @@ fn handle_jump(cmd: &ArgMatches) -> PaziResult {
         }
     };
 
-    let mut matches = match cmd.value_of("dir_target") {
-        Some(to) => {
+    let stats = match cmd.value_of("autojumper") {
+        Some("fasd") => match importers::Fasd::import(&mut frecency) {
+            Ok(stats) => stats,
+            Err(e) => {
+                println!("error importing: {:?}", e);
+                return PaziResult::Error;
+            }
+        },
+        Some(s) => {
+            println!("{}\n\nUnsupported import target: {}", cmd.usage(), s);
+            return PaziResult::Error;
+        }
+        None => {
+            println!("{}\n\nimport requires an argument", cmd.usage());
+            return PaziResult::Error;
+        }
+    };
+
+    match frecency.save_to_disk() {
+        Ok(_) => {
+            println!(
+                "imported {} items from fasd (out of {} in its db)",
+                stats.items_visited, stats.items_considered
+            );
+            PaziResult::Success
+        }
+        Err(e) => {
+            println!("pazi: error adding directory: {:?}", e);
+            PaziResult::Error
+        }
+    }
+}
+
+fn handle_jump(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    let query = query_terms(cmd);
+    let query = query.as_deref();
+    let pattern = match query_pattern(cmd, query) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+    // Named pins are jumped to directly
+    if let Some(dir) = query
+        .filter(|_| pattern.is_none())
+        .and_then(|q| frecency.pinned_dir_named(q))
+    {
+        if Path::new(dir).is_dir() {
+            print!("{}", dir);
+            return PaziResult::SuccessDirectory;
+        }
+        debug!("pinned directory {} does not exist, searching instead", dir);
+    }
+
+    // An explicit '--pipe' wins, but otherwise a configured pipe program replaces the
+    // interactive filter
+    let pipe = cmd.value_of("pipe").or(config
+        .jump
+        .pipe
+        .as_deref()
+        .filter(|_| cmd.is_present("interactive")));
+
+    // As are paths, such as '~/src' or '../sibling', which exist. They're added to the database if
+    // they aren't in it already; the visit itself is recorded by the shell's hook once it has
+    // changed directory. When selecting interactively, the choice is instead between the
+    // directory and those beneath it.
+    let mut beneath = None;
+    if let Some(path) = query
+        .filter(|_| pattern.is_none())
+        .zip(path_query::logical_cwd())
+        .and_then(|(q, cwd)| path_query::resolve(q, &cwd))
+    {
+        match path.to_str() {
+            Some(dir) if path.is_dir() => {
+                frecency.maybe_add(&path);
+                if pipe.is_none() && !cmd.is_present("interactive") {
+                    print!("{}", dir);
+                    if let Err(e) = frecency.save_to_disk() {
+                        println!("\npazi: error saving db changes: {:?}", e);
+                        return PaziResult::Error;
+                    }
+                    return PaziResult::SuccessDirectory;
+                }
+                let pattern = format!(
+                    "^{}({}|$)",
+                    regex::escape(dir),
+                    regex::escape(MAIN_SEPARATOR_STR)
+                );
+                beneath =
+                    Some(RegexMatcher::new(&pattern).expect("escaped paths are valid regexes"));
+            }
+            _ => debug!("{} does not exist, searching instead", path.display()),
+        }
+    }
+
+    let mut matches = match (&beneath, &pattern, query) {
+        (Some(beneath), _, _) => frecency.pattern_matches(beneath),
+        (None, Some(p), _) => frecency.pattern_matches(p.as_ref()),
+        (None, None, Some(to)) => {
             env::current_dir()
                 .map(|cwd| {
                     frecency.maybe_add_relative_to(cwd, to);
@@ -478,16 +1302,23 @@ fn handle_jump(cmd: &ArgMatches) -> PaziResult {
                 .unwrap_or(()); // truly ignore failure to get cwd
             frecency.directory_matches(to)
         }
-        None => frecency.items_with_frecency(),
+        (None, None, None) => frecency.items_with_frecency(),
     };
 
-    let res = if cmd.is_present("interactive") {
+    let selection = if let Some(pipe) = pipe {
+        // TODO: this restricts use of valid program :(
+        let pipe_with_args = pipe.split_whitespace().collect();
+        match pipe::pipe(matches, pipe_with_args) {
+            Ok(el) => Some(el),
+            Err(e) => {
+                print!("{}", e);
+                return PaziResult::Error;
+            }
+        }
+    } else if cmd.is_present("interactive") {
         let stdout = termion::get_tty().unwrap();
         match interactive::filter(matches, std::io::stdin(), stdout) {
-            Ok(el) => {
-                print!("{}", el);
-                PaziResult::SuccessDirectory
-            }
+            Ok(el) => Some(el),
             Err(interactive::FilterError::NoSelection) => {
                 // early return since no selection arbitrarily implies not trimming non-existent
                 // paths. The early return skips the save_to_disk below
@@ -498,24 +1329,20 @@ fn handle_jump(cmd: &ArgMatches) -> PaziResult {
                 return PaziResult::Error;
             }
         }
-    } else if let Some(pipe) = cmd.value_of("pipe") {
-        // TODO: this restricts use of valid program :(
-        let pipe_with_args = pipe.split_whitespace().collect();
-        match pipe::pipe(matches, pipe_with_args) {
-            Ok(el) => {
-                print!("{}", el);
-                PaziResult::SuccessDirectory
-            }
-            Err(e) => {
-                print!("{}", e);
-                return PaziResult::Error;
+    } else {
+        matches.next().map(|(path, _)| path)
+    };
+
+    let res = match selection {
+        Some(dir) => {
+            print!("{}", dir);
+            // selections are only learned for fuzzy queries
+            if let (Some(q), None, None) = (query, &pattern, &beneath) {
+                frecency.record_selection(q, &dir);
             }
+            PaziResult::SuccessDirectory
         }
-    } else if let Some((path, _)) = matches.next() {
-        print!("{}", path);
-        PaziResult::SuccessDirectory
-    } else {
-        PaziResult::Error
+        None => PaziResult::Error,
     };
 
     if let Err(e) = frecency.save_to_disk() {
@@ -526,23 +1353,35 @@ fn handle_jump(cmd: &ArgMatches) -> PaziResult {
     res
 }
 
-fn handle_visit(cmd: &ArgMatches) -> PaziResult {
+fn handle_visit(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let leaving = cmd.value_of("leaving");
     let dir = match cmd.value_of("dir_target") {
-        Some(dir) => dir,
+        Some(dir) => Some(dir),
+        // leaving a directory without entering another is fine, e.g. on shell exit
+        None if leaving.is_some() => None,
         None => {
             println!("visit: visit requires a directory target to visit");
             return PaziResult::Error;
         }
     };
 
-    let mut frecency = match load_frecency() {
+    if cmd.is_present("file") {
+        return handle_visit_file(dir.unwrap(), config);
+    }
+
+    let mut frecency = match load_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
             return PaziResult::Error;
         }
     };
-    frecency.visit(dir.to_string());
+    if let Some(left) = leaving {
+        frecency.leave(left);
+    }
+    if let Some(dir) = dir {
+        frecency.visit(dir.to_string());
+    }
 
     match frecency.save_to_disk() {
         Ok(_) => PaziResult::Success,
@@ -553,38 +1392,524 @@ fn handle_visit(cmd: &ArgMatches) -> PaziResult {
     }
 }
 
-fn handle_print_frecency(cmd: &ArgMatches) -> PaziResult {
-    let mut frecency = match load_frecency() {
+fn handle_visit_file(file: &str, config: &Config) -> PaziResult {
+    // Unlike directories, which come from the shell hooks, files are likely to be given by a user
+    // and so may be relative.
+    let file = match std::fs::canonicalize(file) {
+        Ok(file) if file.is_file() => file,
+        Ok(_) => {
+            println!("visit: not a file: {}", file);
+            return PaziResult::Error;
+        }
+        Err(e) => {
+            println!("visit: could not resolve {}: {}", file, e);
+            return PaziResult::Error;
+        }
+    };
+    let file = match file.to_str() {
+        Some(file) => file.to_string(),
+        None => {
+            println!("visit: file is not valid utf-8: {:?}", file);
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_file_frecency(config) {
         Ok(f) => f,
         Err(e) => {
             println!("{:?}", e);
             return PaziResult::Error;
         }
     };
+    frecency.visit(file);
+
+    match frecency.save_to_disk() {
+        Ok(_) => PaziResult::Success,
+        Err(e) => {
+            println!("pazi: error adding file: {:?}", e);
+            PaziResult::Error
+        }
+    }
+}
 
-    let matches = match cmd.value_of("dir_target") {
-        Some(to) => frecency.directory_matches(to),
-        None => frecency.items_with_frecency(),
+fn handle_file(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let mut frecency = match load_file_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
     };
 
-    for el in matches {
-        // precision for floats only handles the floating part, which leads to unaligned
-        // output, e.g., for a precision value of '3', you might get:
-        // 1.000
-        // 100.000
-        //
-        // By converting it to a string first, and then truncating it, we can get nice prettily
-        // aligned strings.
-        // Note: the string's precision should be at least as long as the printed precision so
-        // there are enough characters.
-        let str_val = format!("{:.5}", (el.1 * 100f64));
-        println!("{:.5}\t{}", str_val, el.0);
+    let mut matches = find_matches(&mut frecency, cmd.value_of("file_target"), None);
+    let res = if cmd.is_present("list") {
+        for el in matches {
+            let str_val = format!("{:.5}", (el.1 * 100f64));
+            println!("{:.5}\t{}", str_val, el.0);
+        }
+        PaziResult::Success
+    } else if let Some((path, _)) = matches.next() {
+        println!("{}", path);
+        PaziResult::Success
+    } else {
+        PaziResult::Error
+    };
+
+    // save any nonexistent files which were trimmed
+    if let Err(e) = frecency.save_to_disk() {
+        println!("pazi: error saving db changes: {:?}", e);
+        return PaziResult::Error;
+    }
+    res
+}
+
+fn handle_pin(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let dir = match cmd.value_of("dir_target") {
+        Some(dir) => dir,
+        None => {
+            println!("{}\n\npin requires a directory to pin", cmd.usage());
+            return PaziResult::Error;
+        }
+    };
+    let dir = match std::fs::canonicalize(dir) {
+        Ok(dir) if dir.is_dir() => dir,
+        Ok(_) => {
+            println!("pin: not a directory: {}", dir);
+            return PaziResult::Error;
+        }
+        Err(e) => {
+            println!("pin: could not resolve {}: {}", dir, e);
+            return PaziResult::Error;
+        }
+    };
+    let dir = match dir.to_str() {
+        Some(dir) => dir.to_string(),
+        None => {
+            println!("pin: directory is not valid utf-8: {:?}", dir);
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+    frecency.pin(dir, cmd.value_of("name"));
+
+    match frecency.save_to_disk() {
+        Ok(_) => PaziResult::Success,
+        Err(e) => {
+            println!("pazi: error pinning directory: {:?}", e);
+            PaziResult::Error
+        }
+    }
+}
+
+fn handle_prune(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let older_than = match cmd.value_of("older-than").map(prune::parse_duration) {
+        None => None,
+        Some(Ok(age)) => Some(age),
+        Some(Err(e)) => {
+            println!("prune: {}", e);
+            return PaziResult::Error;
+        }
+    };
+    let below_weight = match cmd.value_of("below-score").map(str::parse::<f64>) {
+        None => None,
+        Some(Ok(weight)) if weight.is_finite() => Some(weight),
+        Some(_) => {
+            println!("prune: --below-score must be a number of visits");
+            return PaziResult::Error;
+        }
+    };
+    let criteria = prune::Criteria {
+        excluded: cmd.is_present("excluded"),
+        older_than,
+        below_weight,
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+    let prunable = frecency.prunable(&criteria);
+    if cmd.is_present("dry-run") {
+        for (path, reason) in prunable {
+            println!("would remove {} ({})", path, reason);
+        }
+        return PaziResult::Success;
+    }
+    for (path, _) in &prunable {
+        frecency.remove(path);
+    }
+
+    match frecency.save_to_disk() {
+        Ok(_) => {
+            for (path, reason) in prunable {
+                println!("removed {} ({})", path, reason);
+            }
+            PaziResult::Success
+        }
+        Err(e) => {
+            println!("pazi: error saving db: {:?}", e);
+            PaziResult::Error
+        }
+    }
+}
+
+// QueryMatch is how each match is printed by 'pazi query --json'
+#[derive(Serialize)]
+struct QueryMatch<'a> {
+    path: &'a str,
+    score: f64,
+}
+
+fn handle_query(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let limit = match cmd.value_of("limit").map(str::parse::<usize>) {
+        None => usize::MAX,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            println!("query: --limit must be a whole number");
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    let query = query_terms(cmd);
+    let pattern = match query_pattern(cmd, query.as_deref()) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    // Querying is read-only, so missing directories are never trimmed from the database
+    let missing = if cmd.is_present("exists-only") {
+        Missing::Skip
+    } else {
+        Missing::Keep
+    };
+    // The shell's idea of the current directory may differ from the real one because of symlinks
+    let cwds: Vec<PathBuf> = if cmd.is_present("exclude-cwd") {
+        [path_query::logical_cwd(), env::current_dir().ok()]
+            .into_iter()
+            .flatten()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let matches: Vec<_> = find_matches(&mut frecency, query.as_deref(), pattern.as_deref())
+        .missing(missing)
+        .filter(|(path, _)| !cwds.iter().any(|cwd| cwd == Path::new(path)))
+        .take(limit)
+        .collect();
+
+    if cmd.is_present("json") {
+        let json: Vec<_> = matches
+            .iter()
+            .map(|(path, score)| QueryMatch {
+                path,
+                score: score * 100.0,
+            })
+            .collect();
+        match serde_json::to_string(&json) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                println!("pazi: could not serialize matches: {}", e);
+                return PaziResult::Error;
+            }
+        }
+    } else {
+        let terminator = if cmd.is_present("null") { '\0' } else { '\n' };
+        for (path, score) in &matches {
+            if cmd.is_present("score") {
+                // see handle_print_frecency for why this is formatted twice
+                let str_val = format!("{:.5}", score * 100.0);
+                print!("{:.5}\t{}{}", str_val, path, terminator);
+            } else {
+                print!("{}{}", path, terminator);
+            }
+        }
+    }
+
+    // Like grep, finding nothing is a failure so scripts can tell
+    if matches.is_empty() {
+        PaziResult::Error
+    } else {
+        PaziResult::Success
     }
+}
 
+fn handle_remove(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let pattern = match query_pattern(cmd, cmd.value_of("match")) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    // whether any argument could select more than one directory
+    let mut bulk = false;
+    let mut targets = Vec::new();
+    if let Some(query) = cmd.value_of("match") {
+        // Fuzzy matching is too loose to delete by, so only exact substrings, regexes and globs
+        // select directories to remove.
+        match pattern {
+            Some(p) => {
+                // Match against a copy so missing directories aren't trimmed as a side effect
+                let mut fclone = frecency.clone();
+                targets.extend(fclone.pattern_matches_raw(p.as_ref()).map(|(path, _)| path));
+            }
+            None => targets.extend(frecency.substring_matches(query)),
+        }
+        if targets.is_empty() {
+            println!("remove: nothing matches {}", query);
+            return PaziResult::Error;
+        }
+        bulk = true;
+    }
+
+    let cwd = match env::current_dir() {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            println!("remove: could not get current directory: {}", e);
+            return PaziResult::Error;
+        }
+    };
+    for target in cmd.values_of("dir_target").into_iter().flatten() {
+        // The directory may no longer exist, so it can't necessarily be canonicalized
+        let path = path_query::normalize(&cwd.join(target));
+        if is_glob(target) {
+            let glob = match GlobMatcher::new(&path.to_string_lossy()) {
+                Ok(glob) => glob,
+                Err(e) => {
+                    println!("remove: invalid glob '{}': {}", target, e);
+                    return PaziResult::Error;
+                }
+            };
+            let len = targets.len();
+            targets.extend(
+                frecency
+                    .paths()
+                    .into_iter()
+                    .filter(|p| glob.matches(p, "").is_some()),
+            );
+            if targets.len() == len {
+                println!("remove: nothing in the database matches {}", target);
+                return PaziResult::Error;
+            }
+            bulk = true;
+            continue;
+        }
+        let candidates = [
+            path.to_str().map(str::to_string),
+            std::fs::canonicalize(&path)
+                .ok()
+                .and_then(|p| p.to_str().map(str::to_string)),
+        ];
+        match candidates
+            .into_iter()
+            .flatten()
+            .find(|p| frecency.contains(p))
+        {
+            Some(path) => targets.push(path),
+            None => {
+                println!("remove: not in the database: {}", target);
+                return PaziResult::Error;
+            }
+        }
+    }
+    targets.sort();
+    targets.dedup();
+
+    let dry_run = cmd.is_present("dry-run");
+    if dry_run || (bulk && targets.len() > 1 && !cmd.is_present("yes")) {
+        for path in &targets {
+            println!("would remove {}", path);
+        }
+        if dry_run {
+            return PaziResult::Success;
+        }
+        println!(
+            "remove: pass --yes to remove these {} directories",
+            targets.len()
+        );
+        return PaziResult::Error;
+    }
+
+    if let Err(e) = frecency.apply_diff(PathFrecencyDiff::new(Vec::new(), targets.clone())) {
+        println!("pazi: error removing directories: {:?}", e);
+        return PaziResult::Error;
+    }
+    if let Err(e) = frecency.save_to_disk() {
+        println!("pazi: error saving db: {:?}", e);
+        return PaziResult::Error;
+    }
+    for path in targets {
+        println!("removed {}", path);
+    }
     PaziResult::Success
 }
 
-fn frecency_path() -> Result<PathBuf, String> {
+fn handle_unpin(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    let target = match cmd.value_of("target") {
+        Some(target) => target,
+        None => {
+            println!(
+                "{}\n\nunpin requires a directory or name to unpin",
+                cmd.usage()
+            );
+            return PaziResult::Error;
+        }
+    };
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+    // Allow relative paths as long as they don't shadow a name
+    let unpinned = frecency.unpin(target).or_else(|| {
+        std::fs::canonicalize(target)
+            .ok()
+            .and_then(|dir| dir.to_str().map(|d| d.to_string()))
+            .and_then(|dir| frecency.unpin(&dir))
+    });
+    if unpinned.is_none() {
+        println!("unpin: no pinned directory or name matching {}", target);
+        return PaziResult::Error;
+    }
+
+    match frecency.save_to_disk() {
+        Ok(_) => PaziResult::Success,
+        Err(e) => {
+            println!("pazi: error unpinning directory: {:?}", e);
+            PaziResult::Error
+        }
+    }
+}
+
+fn handle_print_frecency(cmd: &ArgMatches, config: &Config) -> PaziResult {
+    // These are all restricted to their possible values, so they always parse
+    let fields: Option<Vec<view::Field>> = cmd.values_of("fields").map(|fields| {
+        fields
+            .map(|f| f.parse().expect("fields are validated by clap"))
+            .collect()
+    });
+    let format = match cmd.value_of("format").map(str::parse) {
+        Some(format) => format.expect("format is validated by clap"),
+        // asking for fields implies tsv
+        None if fields.is_some() => view::Format::Tsv,
+        None if termion::is_tty(&std::io::stdout()) => view::Format::Table,
+        None => view::Format::Tsv,
+    };
+    if fields.is_some() && format != view::Format::Tsv {
+        println!("view: --fields only applies to --format tsv");
+        return PaziResult::Error;
+    }
+    let sort = cmd
+        .value_of("sort")
+        .and_then(|sort| sort.parse().ok())
+        .unwrap_or(view::Sort::Score);
+    let limit = match cmd.value_of("limit").map(str::parse::<usize>) {
+        None => usize::MAX,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            println!("view: --limit must be a whole number");
+            return PaziResult::Error;
+        }
+    };
+    let raw = cmd.is_present("raw");
+
+    let mut frecency = match load_frecency(config) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("{:?}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    let query = query_terms(cmd);
+    let pattern = match query_pattern(cmd, query.as_deref()) {
+        Ok(p) => p,
+        Err(e) => {
+            println!("pazi: {}", e);
+            return PaziResult::Error;
+        }
+    };
+
+    let pins = frecency.pins();
+    let matches: Vec<_> = if raw {
+        match (&pattern, query.as_deref()) {
+            (Some(p), _) => frecency.pattern_matches_raw(p.as_ref()),
+            (None, Some(q)) => frecency.directory_matches_raw(q),
+            (None, None) => frecency.items_with_frecency_raw(),
+        }
+        .collect()
+    } else {
+        find_matches(&mut frecency, query.as_deref(), pattern.as_deref()).collect()
+    };
+
+    let mut entries: Vec<_> = matches
+        .into_iter()
+        .map(|(path, score)| {
+            let stats = frecency.visit_stats(&path);
+            let pin = pins.get(&path);
+            view::Entry {
+                score: if raw { score } else { score * 100.0 },
+                visits: stats.map(|s| s.visits),
+                last_visit: stats.map(|s| s.last_visit),
+                pinned: pin.is_some(),
+                pin_name: pin.cloned().flatten(),
+                path,
+            }
+        })
+        .collect();
+    view::sort(&mut entries, sort);
+    entries.truncate(limit);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let fields = fields.as_deref().unwrap_or(view::DEFAULT_FIELDS);
+    match view::write(&mut std::io::stdout().lock(), &entries, format, fields, now) {
+        Ok(()) => PaziResult::Success,
+        // e.g. 'pazi view | head'
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => PaziResult::Success,
+        Err(e) => {
+            println!("pazi: could not print the database: {}", e);
+            PaziResult::Error
+        }
+    }
+}
+
+fn config_dir() -> Result<PathBuf, String> {
     let project_dir = directories::ProjectDirs::from("", "", "pazi")
         .ok_or_else(|| "unable to determine config path".to_string())?;
     let config_dir = project_dir.config_dir();
@@ -592,8 +1917,7 @@ fn frecency_path() -> Result<PathBuf, String> {
     std::fs::create_dir_all(config_dir)
         .map_err(|e| format!("could not create config dir: {}", e))?;
 
-    let db_path = config_dir.join(PAZI_DB_NAME);
-    Ok(db_path)
+    Ok(config_dir.to_path_buf())
 }
 
 fn intercept_ctrl_c() -> Result<(), ()> {
@@ -1,7 +1,275 @@
-use std::path::Path;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::MAIN_SEPARATOR;
 
-pub trait Matcher {
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+pub trait Matcher: Sync {
     fn matches(&self, input: &str, search: &str) -> Option<f64>;
+
+    // explain returns both the score of the innermost matcher, before any attenuation by the
+    // matchers wrapping it, and the final score 'matches' returns.
+    fn explain(&self, input: &str, search: &str) -> Option<(f64, f64)> {
+        self.matches(input, search).map(|score| (score, score))
+    }
+
+    // explain_text is 'explain' for text which may have already been indexed. Matchers which wrap
+    // other matchers should implement it so that the work in the index is shared between them,
+    // rather than each case folding and splitting the same paths over again.
+    fn explain_text(&self, input: &Text, search: &Text) -> Option<(f64, f64)> {
+        self.explain(input.text, search.text)
+    }
+
+    // name describes the matcher for humans, e.g. "path component(substring)"
+    fn name(&self) -> String;
+}
+
+// IndexedText is a path or query along with the parts of it matchers commonly need, computed once
+// up front.
+#[derive(Clone, Debug)]
+pub struct IndexedText {
+    text: String,
+    folded: String,
+    components: Vec<Range<usize>>,
+    folded_components: Vec<Range<usize>>,
+}
+
+impl IndexedText {
+    pub fn new(text: &str) -> Self {
+        let folded = case_fold(text);
+        IndexedText {
+            components: component_ranges(text),
+            folded_components: component_ranges(&folded),
+            text: text.to_string(),
+            folded,
+        }
+    }
+
+    pub fn text(&self) -> Text<'_> {
+        Text {
+            text: &self.text,
+            folded: Some(&self.folded),
+            components: Some((&self.components, &self.folded_components)),
+        }
+    }
+}
+
+// Ranges are the byte ranges of each path component of some text.
+type Ranges = [Range<usize>];
+
+// Text is a view of some text to match along with, if they're known, its case folded form and
+// the byte ranges of its path components in both. Text which isn't indexed is case folded and
+// split by the matchers which need it, when they need it.
+#[derive(Clone, Copy)]
+pub struct Text<'a> {
+    pub text: &'a str,
+    folded: Option<&'a str>,
+    components: Option<(&'a Ranges, &'a Ranges)>,
+}
+
+impl<'a> Text<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Text {
+            text,
+            folded: None,
+            components: None,
+        }
+    }
+
+    fn with_folded(text: &'a str, folded: Option<&'a str>) -> Self {
+        Text {
+            text,
+            folded,
+            components: None,
+        }
+    }
+
+    // folded returns the case folded text, folding it first if it isn't known.
+    pub fn folded(&self) -> Cow<'a, str> {
+        match self.folded {
+            Some(folded) => Cow::Borrowed(folded),
+            None => Cow::Owned(case_fold(self.text)),
+        }
+    }
+
+    // components returns each path component of the text along with its case folded form, if
+    // that's known. Like 'Path::components', the root of an absolute path is its first component.
+    fn components(&self) -> Vec<Text<'a>> {
+        let text = self.text;
+        let folded = match self.folded {
+            Some(folded) => folded,
+            None => {
+                return component_ranges(text)
+                    .into_iter()
+                    .map(|range| Text::new(&text[range]))
+                    .collect()
+            }
+        };
+        let (ranges, folded_ranges): (Cow<Ranges>, Cow<Ranges>) = match self.components {
+            Some((ranges, folded_ranges)) => (ranges.into(), folded_ranges.into()),
+            None => (
+                component_ranges(text).into(),
+                component_ranges(folded).into(),
+            ),
+        };
+        ranges
+            .iter()
+            .zip(folded_ranges.iter())
+            .map(|(range, folded_range)| {
+                Text::with_folded(&text[range.clone()], Some(&folded[folded_range.clone()]))
+            })
+            .collect()
+    }
+
+    // case_folded returns the text's case folded form, as returned by 'folded', as text of its own.
+    fn case_folded<'b>(&self, folded: &'b str) -> Text<'b>
+    where
+        'a: 'b,
+    {
+        Text {
+            text: folded,
+            folded: Some(folded),
+            components: self.components.map(|(_, folded)| (folded, folded)),
+        }
+    }
+}
+
+fn component_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut res = Vec::new();
+    if text.starts_with(MAIN_SEPARATOR) {
+        res.push(0..MAIN_SEPARATOR.len_utf8());
+    }
+    let mut start = 0;
+    for (ndx, c) in text.char_indices() {
+        if c == MAIN_SEPARATOR {
+            if ndx > start {
+                res.push(start..ndx);
+            }
+            start = ndx + c.len_utf8();
+        }
+    }
+    if text.len() > start {
+        res.push(start..text.len());
+    }
+    res
+}
+
+// case_fold folds the case of text, such that text differing only in case compares equal.
+fn case_fold(text: &str) -> String {
+    // folding ascii is lowercasing it, which is much faster, and most paths are ascii
+    if text.is_ascii() {
+        text.to_ascii_lowercase()
+    } else {
+        caseless::default_case_fold_str(text)
+    }
+}
+
+// strip_diacritics decomposes text and removes its diacritics, so "café" becomes "cafe". This also
+// means strings which only differ in their unicode normalization form become equal.
+fn strip_diacritics(text: &str) -> Cow<'_, str> {
+    // ascii has nothing to decompose, and most paths are ascii
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(text.nfkd().filter(|c| !is_combining_mark(*c)).collect())
+}
+
+// fold strips the diacritics from text and, if case is ignored, folds its case. Matchers which
+// compare text character by character use it to treat case and accents the way the transformed
+// matchers do.
+fn fold(text: &str, ignore_case: bool) -> Cow<'_, str> {
+    let stripped = strip_diacritics(text);
+    if ignore_case {
+        Cow::Owned(case_fold(&stripped))
+    } else {
+        stripped
+    }
+}
+
+// folds_to_subsequence returns whether the folded search appears in order in the input once it's
+// folded the same way. It's much cheaper than scoring a subsequence or acronym match, so those
+// matchers use it to rule most inputs out first.
+fn folds_to_subsequence(input: &str, search: &[char], ignore_case: bool) -> bool {
+    let mut remaining = search;
+    let mut advance = |c: char| {
+        if let Some((first, rest)) = remaining.split_first() {
+            if *first == c {
+                remaining = rest;
+            }
+        }
+    };
+    for c in input.chars() {
+        if c.is_ascii() {
+            advance(if ignore_case {
+                c.to_ascii_lowercase()
+            } else {
+                c
+            });
+        } else {
+            fold(c.encode_utf8(&mut [0; 4]), ignore_case)
+                .chars()
+                .for_each(&mut advance);
+        }
+    }
+    remaining.is_empty()
+}
+
+// MatchOptions are user preferences which affect which matchers are used for a query, and how
+// their matches are weighted.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchOptions {
+    // smart_case makes queries containing an uppercase letter match case-sensitively
+    pub smart_case: bool,
+    pub weights: MatchWeights,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            smart_case: true,
+            weights: MatchWeights::default(),
+        }
+    }
+}
+
+// MatchWeights tune how matches are ranked against each other. The defaults are documented in
+// docs/usage/matching.md, which should be kept up to date with them.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchWeights {
+    // match_ratio is how much of a directory's score comes from how well it matched the query;
+    // the rest comes from its frecency.
+    pub match_ratio: f64,
+    // case_insensitive attenuates matches which only matched once case was ignored
+    pub case_insensitive: f64,
+    // non_prefix_substring attenuates substring matches which aren't at the start of the input
+    pub non_prefix_substring: f64,
+    // punctuation_insensitive attenuates matches which only matched once punctuation was removed
+    pub punctuation_insensitive: f64,
+    // diacritic_insensitive attenuates matches which only matched once diacritics were removed
+    pub diacritic_insensitive: f64,
+    // Matches in path components are weighted component_falloff_start in the final component,
+    // and linearly less towards component_falloff_end the further left they are.
+    pub component_falloff_start: f64,
+    pub component_falloff_end: f64,
+}
+
+impl Default for MatchWeights {
+    fn default() -> Self {
+        MatchWeights {
+            match_ratio: 0.8,
+            case_insensitive: 0.7,
+            non_prefix_substring: 0.8,
+            punctuation_insensitive: 0.8,
+            diacritic_insensitive: 0.9,
+            component_falloff_start: 0.9,
+            component_falloff_end: 0.2,
+        }
+    }
 }
 
 pub struct ExactMatcher {}
@@ -12,9 +280,25 @@ impl Matcher for ExactMatcher {
         }
         None
     }
+
+    fn name(&self) -> String {
+        "exact".to_string()
+    }
+}
+
+pub struct SubstringMatcher {
+    // non_prefix_weight is the weight of a match anywhere but the start of the input
+    pub non_prefix_weight: f64,
+}
+
+impl Default for SubstringMatcher {
+    fn default() -> Self {
+        SubstringMatcher {
+            non_prefix_weight: MatchWeights::default().non_prefix_substring,
+        }
+    }
 }
 
-pub struct SubstringMatcher {}
 impl Matcher for SubstringMatcher {
     fn matches(&self, input: &str, search: &str) -> Option<f64> {
         let res = input.find(search);
@@ -25,48 +309,432 @@ impl Matcher for SubstringMatcher {
                     // If the match is at the very beginning, consider it a better match
                     1.0
                 } else {
-                    0.8
+                    self.non_prefix_weight
                 };
                 Some(base * search.len() as f64 / input.len() as f64)
             }
         }
     }
+
+    fn name(&self) -> String {
+        "substring".to_string()
+    }
+}
+
+// MultiTermMatcher matches queries made up of several whitespace separated terms, such as
+// "work api". Each term must appear in the path in order, and the last term must appear in the
+// final path component. Queries of a single term never match.
+pub struct MultiTermMatcher {
+    // non_prefix_weight is the weight of a last term which isn't at the start of the final
+    // component
+    pub non_prefix_weight: f64,
+}
+
+impl Default for MultiTermMatcher {
+    fn default() -> Self {
+        MultiTermMatcher {
+            non_prefix_weight: MatchWeights::default().non_prefix_substring,
+        }
+    }
+}
+
+impl Matcher for MultiTermMatcher {
+    fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        let terms: Vec<&str> = search.split_whitespace().collect();
+        let (last, rest) = match terms.split_last() {
+            Some((last, rest)) if !rest.is_empty() => (last, rest),
+            _ => return None,
+        };
+
+        let mut offset = 0;
+        for term in rest {
+            offset += input[offset..].find(term)? + term.len();
+        }
+
+        let last_component_start = input.rfind(MAIN_SEPARATOR).map_or(0, |ndx| ndx + 1);
+        let start = offset.max(last_component_start);
+        let last_offset = start + input[start..].find(last)?;
+
+        // Weight it the same way a substring match on the final component would be weighted
+        let base = if last_offset == last_component_start {
+            1.0
+        } else {
+            self.non_prefix_weight
+        };
+        Some(base * last.len() as f64 / (input.len() - last_component_start) as f64)
+    }
+
+    fn name(&self) -> String {
+        "multi-term".to_string()
+    }
+}
+
+// SubsequenceMatcher matches if every character of the search appears in the input in order,
+// e.g. "prjapi" matches "project-api". Matches are scored similarly to fzf: characters at the start
+// of words (after punctuation or at a camelCase boundary) and runs of consecutive characters score
+// more highly, while gaps between matched characters are penalized.
+// Scores are scaled such that a subsequence match is always worth less than a substring match of
+// the same length on the same input.
+pub struct SubsequenceMatcher {
+    pub ignore_case: bool,
+}
+
+const SUBSEQ_SCORE_MATCH: i64 = 16;
+const SUBSEQ_GAP_START: i64 = -3;
+const SUBSEQ_GAP_EXTENSION: i64 = -1;
+const SUBSEQ_BONUS_BOUNDARY: i64 = SUBSEQ_SCORE_MATCH / 2;
+const SUBSEQ_BONUS_CAMEL: i64 = SUBSEQ_BONUS_BOUNDARY - 1;
+const SUBSEQ_BONUS_CONSECUTIVE: i64 = -(SUBSEQ_GAP_START + SUBSEQ_GAP_EXTENSION);
+const SUBSEQ_FIRST_CHAR_MULTIPLIER: i64 = 2;
+const SUBSEQ_MAX_WEIGHT: f64 = 0.5;
+
+impl SubsequenceMatcher {
+    fn bonus(prev: Option<char>, cur: char) -> i64 {
+        match prev {
+            None => SUBSEQ_BONUS_BOUNDARY,
+            Some(p) if !p.is_alphanumeric() => SUBSEQ_BONUS_BOUNDARY,
+            Some(p) if p.is_lowercase() && cur.is_uppercase() => SUBSEQ_BONUS_CAMEL,
+            Some(p) if !p.is_numeric() && cur.is_numeric() => SUBSEQ_BONUS_CAMEL,
+            _ => 0,
+        }
+    }
+}
+
+impl Matcher for SubsequenceMatcher {
+    fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        let search: Vec<char> = fold(search, self.ignore_case).chars().collect();
+        if search.is_empty() || !folds_to_subsequence(input, &search, self.ignore_case) {
+            return None;
+        }
+
+        // Bonuses depend on the input's case, so they're found before folding it. A character
+        // which folds to several, such as 'ß', only gets its bonus on the first of them.
+        let original: Vec<char> = input.chars().collect();
+        let mut input = Vec::with_capacity(original.len());
+        let mut bonuses = Vec::with_capacity(original.len());
+        for (ndx, &c) in original.iter().enumerate() {
+            let bonus = Self::bonus(ndx.checked_sub(1).map(|p| original[p]), c);
+            if c.is_ascii() {
+                input.push(if self.ignore_case {
+                    c.to_ascii_lowercase()
+                } else {
+                    c
+                });
+                bonuses.push(bonus);
+                continue;
+            }
+            for (i, folded) in fold(c.encode_utf8(&mut [0; 4]), self.ignore_case)
+                .chars()
+                .enumerate()
+            {
+                input.push(folded);
+                bonuses.push(if i == 0 { bonus } else { 0 });
+            }
+        }
+
+        // prev[j] is the best score of matching the search up to the previous character, with
+        // that character matched at input[j]
+        let mut prev: Vec<Option<i64>> = vec![None; input.len()];
+        for (i, &s) in search.iter().enumerate() {
+            let mut cur = vec![None; input.len()];
+            // the best score for the previous character with a gap before j
+            let mut gapped: Option<i64> = None;
+            for j in 0..input.len() {
+                let from_prev = if i == 0 {
+                    Some(bonuses[j] * (SUBSEQ_FIRST_CHAR_MULTIPLIER - 1))
+                } else {
+                    let consecutive = j
+                        .checked_sub(1)
+                        .and_then(|p| prev[p])
+                        .map(|p| p + SUBSEQ_BONUS_CONSECUTIVE.max(bonuses[j]) - bonuses[j]);
+                    consecutive.max(gapped)
+                };
+                if s == input[j] {
+                    cur[j] = from_prev.map(|score| score + SUBSEQ_SCORE_MATCH + bonuses[j]);
+                }
+                if i > 0 {
+                    let gap_from_here = j.checked_sub(1).and_then(|p| prev[p]);
+                    gapped = gapped
+                        .map(|g| g + SUBSEQ_GAP_EXTENSION)
+                        .max(gap_from_here.map(|g| g + SUBSEQ_GAP_START));
+                }
+            }
+            prev = cur;
+        }
+
+        let best = prev.into_iter().flatten().max()?;
+        let max_possible = search.len() as i64 * (SUBSEQ_SCORE_MATCH + SUBSEQ_BONUS_BOUNDARY)
+            + SUBSEQ_BONUS_BOUNDARY * (SUBSEQ_FIRST_CHAR_MULTIPLIER - 1);
+        let quality = (best as f64 / max_possible as f64).clamp(0.0, 1.0);
+        Some(SUBSEQ_MAX_WEIGHT * quality * search.len() as f64 / input.len() as f64)
+    }
+
+    fn name(&self) -> String {
+        "subsequence".to_string()
+    }
 }
 
-pub struct PathComponentMatcher<'a>(&'a dyn Matcher);
+// AcronymMatcher matches if the search is a run of the initials of the words making up the input,
+// e.g. "gep" matches "github-enterprise-provisioning" and "koc" matches
+// "KubernetesOperatorController". Words are split on punctuation and case boundaries.
+// Matches are weighted below a substring match covering a good part of the input, but above a
+// subsequence match of the same search on the same input.
+pub struct AcronymMatcher {
+    pub ignore_case: bool,
+    // non_prefix_weight is the weight of initials which don't start at the start of the input
+    pub non_prefix_weight: f64,
+}
+
+impl Default for AcronymMatcher {
+    fn default() -> Self {
+        AcronymMatcher {
+            ignore_case: false,
+            non_prefix_weight: MatchWeights::default().non_prefix_substring,
+        }
+    }
+}
+
+const ACRONYM_MAX_WEIGHT: f64 = 0.3;
+
+impl AcronymMatcher {
+    // initials returns the first character of each word in the input
+    fn initials(input: &str) -> Vec<char> {
+        let chars: Vec<char> = input.chars().collect();
+        chars
+            .iter()
+            .enumerate()
+            .filter(|&(ndx, &c)| {
+                if !c.is_alphanumeric() {
+                    return false;
+                }
+                let prev = match ndx.checked_sub(1) {
+                    None => return true,
+                    Some(p) => chars[p],
+                };
+                let next = chars.get(ndx + 1);
+                !prev.is_alphanumeric()
+                    || (!prev.is_uppercase() && c.is_uppercase())
+                    // the last capital of a run followed by lowercase starts a word, as in
+                    // 'HTTPServer'
+                    || (prev.is_uppercase()
+                        && c.is_uppercase()
+                        && next.is_some_and(|n| n.is_lowercase()))
+            })
+            .map(|(_, &c)| c)
+            .collect()
+    }
+}
+
+impl Matcher for AcronymMatcher {
+    fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        let search: Vec<char> = fold(search, self.ignore_case).chars().collect();
+        // a single character is better left to other matchers, and anything but letters and
+        // digits can't be initials
+        if search.len() < 2
+            || !search.iter().all(|c| c.is_alphanumeric())
+            // initials are a subsequence of the input, so most inputs are ruled out cheaply
+            || !folds_to_subsequence(input, &search, self.ignore_case)
+        {
+            return None;
+        }
+        // words are found by their case, so the initials are only folded once they're known
+        let initials: String = Self::initials(input).into_iter().collect();
+        let initials: Vec<char> = fold(&initials, self.ignore_case).chars().collect();
+        let offset = initials
+            .windows(search.len())
+            .position(|window| window == &search[..])?;
+        // like substring matches, acronyms of the start of the input are better matches
+        let base = if offset == 0 {
+            1.0
+        } else {
+            self.non_prefix_weight
+        };
+        Some(ACRONYM_MAX_WEIGHT * base * search.len() as f64 / initials.len() as f64)
+    }
+
+    fn name(&self) -> String {
+        "acronym".to_string()
+    }
+}
+
+// LevenshteinMatcher matches inputs within a small edit distance of the search, which allows
+// typos such as "kuberentes" to match "kubernetes". The permitted distance grows with the length
+// of the search, and matches are weighted low since they're fairly likely to be wrong.
+pub struct LevenshteinMatcher {}
+
+const LEVENSHTEIN_MAX_WEIGHT: f64 = 0.3;
+
+impl LevenshteinMatcher {
+    // max_distance is the largest edit distance tolerated for a search of the given length: one
+    // edit per four characters, rounded up.
+    fn max_distance(search_len: usize) -> usize {
+        search_len.div_ceil(4)
+    }
+
+    // distance returns the edit distance between lhs and rhs, or None if it exceeds max.
+    fn distance(lhs: &[char], rhs: &[char], max: usize) -> Option<usize> {
+        if lhs.len().abs_diff(rhs.len()) > max {
+            return None;
+        }
+        let mut prev: Vec<usize> = (0..=rhs.len()).collect();
+        for (i, l) in lhs.iter().enumerate() {
+            let mut cur = vec![i + 1; rhs.len() + 1];
+            for (j, r) in rhs.iter().enumerate() {
+                let substitution = prev[j] + if l == r { 0 } else { 1 };
+                cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+            }
+            // distances only grow from here, so give up early if they're all too far
+            if cur.iter().all(|d| *d > max) {
+                return None;
+            }
+            prev = cur;
+        }
+        Some(prev[rhs.len()]).filter(|d| *d <= max)
+    }
+}
+
+impl Matcher for LevenshteinMatcher {
+    fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        let search: Vec<char> = search.chars().collect();
+        if search.is_empty() {
+            return None;
+        }
+        // most inputs are ruled out by their length alone, which is cheaper to check than
+        // collecting them
+        let max = Self::max_distance(search.len());
+        if input.chars().count().abs_diff(search.len()) > max {
+            return None;
+        }
+        let input: Vec<char> = input.chars().collect();
+        let distance = Self::distance(&input, &search, max)?;
+        let longest = input.len().max(search.len());
+        Some(LEVENSHTEIN_MAX_WEIGHT * (1.0 - distance as f64 / longest as f64))
+    }
+
+    fn name(&self) -> String {
+        "levenshtein".to_string()
+    }
+}
+
+// RegexMatcher matches paths against a regular expression given at construction, e.g.
+// "svc-[0-9]+$". The search passed to 'matches' is ignored.
+// Every matching path is weighted equally, leaving frecency to rank them.
+pub struct RegexMatcher {
+    regex: Regex,
+}
+
+impl RegexMatcher {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(RegexMatcher {
+            regex: Regex::new(pattern)?,
+        })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn matches(&self, input: &str, _search: &str) -> Option<f64> {
+        if self.regex.is_match(input) {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> String {
+        "regex".to_string()
+    }
+}
+
+// GlobMatcher matches whole paths against a glob given at construction, e.g. "**/deploy/*prod*".
+// As in a shell, '*' doesn't match across path separators; '**' does.
+// As with RegexMatcher, the search is ignored and every match is weighted equally.
+pub struct GlobMatcher {
+    pattern: glob::Pattern,
+}
+
+// GLOB_OPTIONS are how every glob pazi is given is matched against paths
+pub const GLOB_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+impl GlobMatcher {
+    pub fn new(pattern: &str) -> Result<Self, glob::PatternError> {
+        Ok(GlobMatcher {
+            pattern: glob::Pattern::new(pattern)?,
+        })
+    }
+}
+
+impl Matcher for GlobMatcher {
+    fn matches(&self, input: &str, _search: &str) -> Option<f64> {
+        if self.pattern.matches_with(input, GLOB_OPTIONS) {
+            Some(1.0)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> String {
+        "glob".to_string()
+    }
+}
+
+pub struct PathComponentMatcher<'a> {
+    matcher: &'a dyn Matcher,
+    falloff: (f64, f64),
+}
 
 impl<'a> PathComponentMatcher<'a> {
     pub fn new(base: &'a dyn Matcher) -> Self {
-        PathComponentMatcher(base)
+        let weights = MatchWeights::default();
+        PathComponentMatcher {
+            matcher: base,
+            falloff: (
+                weights.component_falloff_start,
+                weights.component_falloff_end,
+            ),
+        }
+    }
+
+    // with_falloff sets the weight of matches in the final component, and the weight matches
+    // further left fall off towards.
+    pub fn with_falloff(self, start: f64, end: f64) -> Self {
+        PathComponentMatcher {
+            falloff: (start, end),
+            ..self
+        }
     }
 }
 
 impl<'a> Matcher for PathComponentMatcher<'a> {
     fn matches(&self, input: &str, search: &str) -> Option<f64> {
-        let p = Path::new(input);
-        let components = p.components();
-        let num_components = p.components().count();
+        self.explain(input, search).map(|(_, score)| score)
+    }
+
+    fn explain(&self, input: &str, search: &str) -> Option<(f64, f64)> {
+        self.explain_text(&Text::new(input), &Text::new(search))
+    }
+
+    fn explain_text(&self, input: &Text, search: &Text) -> Option<(f64, f64)> {
+        let components = input.components();
+        let num_components = components.len();
         // Reduce the weight of components the further from the right they are
         // I've arbitrarily chosen to linearly attenuate them
-        let mut weight = 0.9;
-        let weight_step = (weight - 0.2) / num_components as f64;
+        let (mut weight, end) = self.falloff;
+        let weight_step = (weight - end) / num_components as f64;
         let mut res = None;
-        for component in components.rev() {
-            let s = match component.as_os_str().to_str() {
-                Some(s) => s,
-                None => {
-                    continue;
-                }
-            };
-            if let Some(v) = self.0.matches(s, search) {
+        for component in components.iter().rev() {
+            if let Some((raw, v)) = self.matcher.explain_text(component, search) {
                 let attv = v * weight;
                 res = match res {
-                    None => Some(attv),
-                    Some(existing) => {
+                    None => Some((raw, attv)),
+                    Some((existing_raw, existing)) => {
                         if attv > existing {
-                            Some(attv)
+                            Some((raw, attv))
                         } else {
-                            Some(existing)
+                            Some((existing_raw, existing))
                         }
                     }
                 }
@@ -75,39 +743,222 @@ impl<'a> Matcher for PathComponentMatcher<'a> {
         }
         res
     }
+
+    fn name(&self) -> String {
+        format!("path component({})", self.matcher.name())
+    }
+}
+
+// AdjacentComponentMatcher matches queries containing a path separator, such as "dev/tool", by
+// splitting the query into pieces and matching each piece against consecutive path components
+// with the base matcher, so that "dev/tool" matches "/home/user/dev/my-tool".
+// Like PathComponentMatcher, matches further from the right are weighted lower.
+pub struct AdjacentComponentMatcher<'a> {
+    matcher: &'a dyn Matcher,
+    falloff: (f64, f64),
 }
 
+impl<'a> AdjacentComponentMatcher<'a> {
+    pub fn new(base: &'a dyn Matcher) -> Self {
+        let weights = MatchWeights::default();
+        AdjacentComponentMatcher {
+            matcher: base,
+            falloff: (
+                weights.component_falloff_start,
+                weights.component_falloff_end,
+            ),
+        }
+    }
+
+    // with_falloff is PathComponentMatcher::with_falloff
+    pub fn with_falloff(self, start: f64, end: f64) -> Self {
+        AdjacentComponentMatcher {
+            falloff: (start, end),
+            ..self
+        }
+    }
+}
+
+impl<'a> Matcher for AdjacentComponentMatcher<'a> {
+    fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        self.explain(input, search).map(|(_, score)| score)
+    }
+
+    fn explain(&self, input: &str, search: &str) -> Option<(f64, f64)> {
+        self.explain_text(&Text::new(input), &Text::new(search))
+    }
+
+    fn explain_text(&self, input: &Text, search: &Text) -> Option<(f64, f64)> {
+        let root = MAIN_SEPARATOR.to_string();
+        let pieces: Vec<Text> = search
+            .components()
+            .into_iter()
+            .filter(|p| p.text != root)
+            .collect();
+        if pieces.len() < 2 {
+            return None;
+        }
+        let components: Vec<Text> = input
+            .components()
+            .into_iter()
+            .filter(|c| c.text != root)
+            .collect();
+        if pieces.len() > components.len() {
+            return None;
+        }
+
+        let (mut weight, end) = self.falloff;
+        let weight_step = (weight - end) / (components.len() - pieces.len() + 1) as f64;
+        let mut res: Option<(f64, f64)> = None;
+        for window in components.windows(pieces.len()).rev() {
+            let scores: Option<Vec<(f64, f64)>> = window
+                .iter()
+                .zip(pieces.iter())
+                .map(|(component, piece)| self.matcher.explain_text(component, piece))
+                .collect();
+            if let Some(scores) = scores {
+                let n = scores.len() as f64;
+                let raw = scores.iter().map(|(raw, _)| raw).sum::<f64>() / n;
+                let attv = weight * scores.iter().map(|(_, v)| v).sum::<f64>() / n;
+                if res.is_none_or(|(_, existing)| attv > existing) {
+                    res = Some((raw, attv));
+                }
+            }
+            weight -= weight_step;
+        }
+        res
+    }
+
+    fn name(&self) -> String {
+        format!("adjacent components({})", self.matcher.name())
+    }
+}
+
+// TransformedMatcher matches with its base matcher after transforming both the input and the
+// search. A transformation of None means matching the case folded text.
 pub struct TransformedMatcher<'a> {
-    input_transformation: fn(input: &str) -> String,
-    search_transformation: fn(input: &str) -> String,
+    name: &'static str,
+    transformation: Option<fn(input: &str) -> Cow<'_, str>>,
     matcher: &'a dyn Matcher,
     attenuation: f64,
+    // skip_unchanged makes text the transformation doesn't change a miss
+    skip_unchanged: bool,
 }
 
 pub type CaseInsensitiveMatcher<'a> = TransformedMatcher<'a>;
 
 impl<'a> CaseInsensitiveMatcher<'a> {
     pub fn new(base: &'a dyn Matcher) -> Self {
-        fn transformer(input: &str) -> String {
-            input.to_owned().to_lowercase()
+        TransformedMatcher {
+            name: "case-insensitive",
+            transformation: None,
+            matcher: base,
+            attenuation: MatchWeights::default().case_insensitive,
+            skip_unchanged: false,
+        }
+    }
+
+    // with_attenuation sets how much this matcher's matches are worth relative to its base
+    // matcher's.
+    pub fn with_attenuation(self, attenuation: f64) -> Self {
+        TransformedMatcher {
+            attenuation,
+            ..self
+        }
+    }
+
+    // skip_unchanged makes the matcher skip text its transformation leaves as it is, rather than
+    // matching it with the base matcher. That's only worthwhile when the base matcher has already
+    // been tried on the same text by itself, and would match it no better this time.
+    pub fn skip_unchanged(self) -> Self {
+        TransformedMatcher {
+            skip_unchanged: true,
+            ..self
+        }
+    }
+}
+
+impl<'a> TransformedMatcher<'a> {
+    // punctuation_insensitive matches with all punctuation removed from both the input and the
+    // search, so "myproj" matches "my-proj", "my_proj", and "my.proj". Path separators are kept.
+    pub fn punctuation_insensitive(base: &'a dyn Matcher) -> Self {
+        fn transformer(input: &str) -> Cow<'_, str> {
+            let is_punctuation = |c: char| c != MAIN_SEPARATOR && c.is_ascii_punctuation();
+            if !input.contains(is_punctuation) {
+                return Cow::Borrowed(input);
+            }
+            Cow::Owned(input.chars().filter(|c| !is_punctuation(*c)).collect())
         }
         TransformedMatcher {
-            input_transformation: transformer,
-            search_transformation: transformer,
+            name: "punctuation-insensitive",
+            transformation: Some(transformer),
             matcher: base,
-            attenuation: 0.7,
+            attenuation: MatchWeights::default().punctuation_insensitive,
+            skip_unchanged: false,
+        }
+    }
+}
+
+impl<'a> TransformedMatcher<'a> {
+    // diacritic_insensitive matches with both the input and the search decomposed and stripped of
+    // diacritics, so "cafe" matches "café". This also means strings which only differ in their
+    // unicode normalization form, e.g. due to being synced from a different filesystem, match.
+    pub fn diacritic_insensitive(base: &'a dyn Matcher) -> Self {
+        TransformedMatcher {
+            name: "diacritic-insensitive",
+            transformation: Some(strip_diacritics),
+            matcher: base,
+            attenuation: MatchWeights::default().diacritic_insensitive,
+            skip_unchanged: false,
         }
     }
 }
 
 impl<'a> Matcher for TransformedMatcher<'a> {
     fn matches(&self, input: &str, search: &str) -> Option<f64> {
+        self.explain(input, search).map(|(_, score)| score)
+    }
+
+    fn explain(&self, input: &str, search: &str) -> Option<(f64, f64)> {
+        self.explain_text(&Text::new(input), &Text::new(search))
+    }
+
+    fn explain_text(&self, input: &Text, search: &Text) -> Option<(f64, f64)> {
+        let transform = match self.transformation {
+            None => {
+                let (input_folded, search_folded) = (input.folded(), search.folded());
+                return self
+                    .matcher
+                    .explain_text(
+                        &input.case_folded(&input_folded),
+                        &search.case_folded(&search_folded),
+                    )
+                    .map(|(raw, f)| (raw, f * self.attenuation));
+            }
+            Some(t) => t,
+        };
+        let input = (transform(input.text), input.folded.map(transform));
+        let search = (transform(search.text), search.folded.map(transform));
+        let unchanged = |(text, folded): &(Cow<str>, Option<Cow<str>>)| {
+            matches!(text, Cow::Borrowed(_)) && !matches!(folded, Some(Cow::Owned(_)))
+        };
+        if self.skip_unchanged && unchanged(&input) && unchanged(&search) {
+            return None;
+        }
+        // A transformation may have removed everything there was to match on
+        if input.0.is_empty() || search.0.is_empty() {
+            return None;
+        }
         self.matcher
-            .matches(
-                &(self.input_transformation)(input),
-                &(self.search_transformation)(search),
+            .explain_text(
+                &Text::with_folded(&input.0, input.1.as_deref()),
+                &Text::with_folded(&search.0, search.1.as_deref()),
             )
-            .map(|f| f * self.attenuation)
+            .map(|(raw, f)| (raw, f * self.attenuation))
+    }
+
+    fn name(&self) -> String {
+        format!("{}({})", self.name, self.matcher.name())
     }
 }
 
@@ -121,7 +972,7 @@ mod tests {
                 (Some(lhs), Some(rhs)) => {
                     assert!(rhs > lhs);
                 }
-                _ => assert!(false),
+                _ => panic!("expected both to match"),
             }
         }
     }
@@ -144,6 +995,8 @@ mod tests {
         assert_eq!(ci.matches("FOO", "foo"), Some(0.7));
         assert_eq!(ci.matches("foo", "FOO"), Some(0.7));
         assert_eq!(ci.matches("aSdF", "AsDf"), Some(0.7));
+        // full case folding, not just lowercasing
+        assert_eq!(ci.matches("STRASSE", "straße"), Some(0.7));
     }
 
     #[test]
@@ -159,9 +1012,250 @@ mod tests {
         assert_eq!(pc.matches("/foo/bar", "ar"), None);
     }
 
+    #[test]
+    fn test_diacritic_insensitive_matcher() {
+        let em = ExactMatcher {};
+        let ci_em = CaseInsensitiveMatcher::new(&em);
+        let di = TransformedMatcher::diacritic_insensitive(&ci_em);
+
+        assert!(di.matches("café-app", "cafe-app").is_some());
+        assert!(di.matches("Präsentationen", "prasentationen").is_some());
+        // composed and decomposed forms of the same string
+        assert!(di.matches("cafe\u{301}", "caf\u{e9}").is_some());
+        assert_eq!(em.matches("cafe\u{301}", "caf\u{e9}"), None);
+        assert_eq!(di.matches("café", "cafx"), None);
+    }
+
+    #[test]
+    fn test_punctuation_insensitive_matcher() {
+        let em = ExactMatcher {};
+        let ci_em = CaseInsensitiveMatcher::new(&em);
+        let pi = TransformedMatcher::punctuation_insensitive(&ci_em);
+
+        assert!(pi.matches("my-proj", "myproj").is_some());
+        assert!(pi.matches("my_proj", "myproj").is_some());
+        assert!(pi.matches("My.Proj", "myproj").is_some());
+        assert!(pi.matches("myproj", "my-proj").is_some());
+        assert_eq!(pi.matches("my-proj", "my proj"), None);
+        assert_eq!(pi.matches("---", "-"), None);
+        assert_match_in_order(vec![
+            pi.matches("my-proj", "myproj"),
+            em.matches("myproj", "myproj"),
+        ]);
+
+        let skipping = TransformedMatcher::punctuation_insensitive(&ci_em).skip_unchanged();
+        assert_eq!(
+            skipping.matches("my-proj", "myproj"),
+            pi.matches("my-proj", "myproj")
+        );
+        assert_eq!(skipping.matches("myproj", "myproj"), None);
+    }
+
+    #[test]
+    fn test_adjacent_component_matcher() {
+        let sm = SubstringMatcher::default();
+        let ac = AdjacentComponentMatcher::new(&sm);
+
+        assert_eq!(ac.matches("/home/dev/tool", "dev/tool"), Some(0.9));
+        assert!(ac.matches("/home/dev/my-tool", "dev/tool").is_some());
+        assert_eq!(ac.matches("/home/dev", "dev"), None);
+        // components must be adjacent and in order
+        assert_eq!(ac.matches("/dev/src/tool", "dev/tool"), None);
+        assert_eq!(ac.matches("/tool/dev", "dev/tool"), None);
+        assert_match_in_order(vec![
+            ac.matches("/dev/tool/src", "dev/tool"),
+            ac.matches("/src/dev/my-tool", "dev/tool"),
+            ac.matches("/src/dev/tool", "dev/tool"),
+        ]);
+    }
+
+    #[test]
+    fn test_multi_term_matcher() {
+        let mt = MultiTermMatcher::default();
+
+        assert_eq!(mt.matches("/home/user/work/api", "work api"), Some(1.0));
+        assert_eq!(mt.matches("/work/api", "work"), None);
+        // terms must be in order
+        assert_eq!(mt.matches("/api/work", "work api"), None);
+        // the last term must be in the final component
+        assert_eq!(mt.matches("/work/api/src", "work api"), None);
+        // earlier terms may be in the final component too
+        assert_eq!(mt.matches("/x/workapi", "work api"), Some(0.8 * 3.0 / 7.0));
+        assert_eq!(mt.matches("/x/apiwork", "work api"), None);
+        let mt_half = MultiTermMatcher {
+            non_prefix_weight: 0.5,
+        };
+        assert_eq!(
+            mt_half.matches("/x/workapi", "work api"),
+            Some(0.5 * 3.0 / 7.0)
+        );
+        assert_match_in_order(vec![
+            mt.matches("/work/project/my-api", "work api"),
+            mt.matches("/work/project/api-old", "work api"),
+            mt.matches("/work/project/api", "work api"),
+        ]);
+    }
+
+    #[test]
+    fn test_acronym_matcher() {
+        let am = AcronymMatcher {
+            ignore_case: true,
+            ..Default::default()
+        };
+
+        assert!(am
+            .matches("github-enterprise-provisioning", "gep")
+            .is_some());
+        assert!(am.matches("KubernetesOperatorController", "koc").is_some());
+        assert!(am.matches("my_big.project", "mbp").is_some());
+        assert!(am.matches("HTTPServerConfig", "hsc").is_some());
+        assert!(am.matches("github-enterprise-provisioning", "ep").is_some());
+        assert_eq!(am.matches("github-enterprise-provisioning", "gp"), None);
+        assert_eq!(am.matches("github-enterprise-provisioning", "g"), None);
+        assert_eq!(am.matches("github-enterprise-provisioning", "g-e"), None);
+        assert_eq!(am.matches("kubernetesoperatorcontroller", "koc"), None);
+        let am_half = AcronymMatcher {
+            ignore_case: true,
+            non_prefix_weight: 0.5,
+        };
+        assert_eq!(
+            am_half.matches("github-enterprise-provisioning", "ep"),
+            am.matches("github-enterprise-provisioning", "ep")
+                .map(|score| score * 0.5 / 0.8)
+        );
+        let cs = AcronymMatcher::default();
+        assert_eq!(cs.matches("KubernetesOperatorController", "koc"), None);
+        assert!(cs.matches("KubernetesOperatorController", "KOC").is_some());
+        // case and diacritics are folded like every other matcher folds them
+        assert!(am.matches("Élan-Vital", "ev").is_some());
+        assert!(am.matches("straße-über-alles", "SUA").is_some());
+        assert!(cs.matches("Élan-Vital", "EV").is_some());
+
+        // covering more of the input, and starting at its beginning, is a better match
+        assert_match_in_order(vec![
+            am.matches("github-enterprise-provisioning", "ep"),
+            am.matches("github-enterprise-provisioning", "ge"),
+            am.matches("github-enterprise-provisioning", "gep"),
+        ]);
+
+        // it's weighted between substring and subsequence matches
+        let sm = SubstringMatcher::default();
+        let ss = SubsequenceMatcher { ignore_case: true };
+        assert_match_in_order(vec![
+            ss.matches("github-enterprise-provisioning", "gep"),
+            am.matches("github-enterprise-provisioning", "gep"),
+            sm.matches("gep-tools", "gep"),
+        ]);
+    }
+
+    #[test]
+    fn test_subsequence_matcher() {
+        let ss = SubsequenceMatcher { ignore_case: true };
+
+        assert!(ss.matches("project-api", "prjapi").is_some());
+        assert_eq!(ss.matches("project-api", "ipa"), None);
+        assert_eq!(ss.matches("api", "apis"), None);
+        assert!(ss.matches("Project-API", "prjapi").is_some());
+        let cs = SubsequenceMatcher { ignore_case: false };
+        assert_eq!(cs.matches("Project-API", "prjapi"), None);
+        // case and diacritics are folded like every other matcher folds them
+        assert!(ss.matches("Résumé", "resume").is_some());
+        assert!(ss.matches("Re\u{301}sume\u{301}", "résumé").is_some());
+        assert!(ss.matches("STRASSE-x", "straßex").is_some());
+        assert!(cs.matches("résumé", "resume").is_some());
+        assert_eq!(cs.matches("Résumé", "resume"), None);
+
+        // word boundaries and camelCase humps are favored over matches mid-word
+        assert_match_in_order(vec![
+            ss.matches("xgithubxenterprisexprovisionin", "gep"),
+            ss.matches("github-enterprise-provisioning", "gep"),
+        ]);
+        assert_match_in_order(vec![
+            ss.matches("kubernetesoperatorcontroller", "koc"),
+            ss.matches("KubernetesOperatorController", "koc"),
+        ]);
+        // consecutive characters are favored over gaps
+        assert_match_in_order(vec![
+            ss.matches("axpxixxx", "api"),
+            ss.matches("apixxxxx", "api"),
+        ]);
+
+        // it never outscores a substring match
+        let sm = SubstringMatcher::default();
+        for (input, search) in &[("foobar", "oba"), ("project-api", "api"), ("api", "api")] {
+            assert_match_in_order(vec![ss.matches(input, search), sm.matches(input, search)]);
+        }
+    }
+
+    #[test]
+    fn test_levenshtein_matcher() {
+        let lm = LevenshteinMatcher {};
+
+        assert_eq!(lm.matches("kubernetes", "kubernetes"), Some(0.3));
+        assert!(lm.matches("kubernetes", "kuberentes").is_some());
+        assert!(lm.matches("kubernetes", "kubrnetes").is_some());
+        assert!(lm.matches("kubernetes", "kubernetess").is_some());
+        // too many edits for a short search
+        assert_eq!(lm.matches("foo", "bar"), None);
+        assert_eq!(lm.matches("docs", "dogz"), None);
+        assert_eq!(lm.matches("kubernetes", "kube"), None);
+        assert_match_in_order(vec![
+            lm.matches("kubernetes", "kuberentes"),
+            lm.matches("kubernetes", "kubernetez"),
+            lm.matches("kubernetes", "kubernetes"),
+        ]);
+    }
+
+    #[test]
+    fn test_regex_matcher() {
+        let rm = RegexMatcher::new("svc-[0-9]+$").unwrap();
+
+        assert_eq!(rm.matches("/work/svc-12", ""), Some(1.0));
+        assert_eq!(rm.matches("/work/svc-12/src", ""), None);
+        assert_eq!(rm.matches("/work/svc-a", ""), None);
+        assert!(RegexMatcher::new("svc-[0-9").is_err());
+    }
+
+    #[test]
+    fn test_glob_matcher() {
+        let gm = GlobMatcher::new("**/deploy/*prod*").unwrap();
+
+        assert_eq!(gm.matches("/work/deploy/us-prod-1", ""), Some(1.0));
+        assert_eq!(gm.matches("/deploy/us-prod-1", ""), Some(1.0));
+        assert_eq!(gm.matches("/work/deploy/staging", ""), None);
+        assert!(GlobMatcher::new("[prod").is_err());
+
+        // '*' stays within a single component, while '**' spans any number of them
+        assert_eq!(gm.matches("/work/deploy/us-prod-1/src", ""), None);
+        assert_eq!(gm.matches("/work/deploy/us/prod-1", ""), None);
+        let gm = GlobMatcher::new("/work/*").unwrap();
+        assert_eq!(gm.matches("/work/api", ""), Some(1.0));
+        assert_eq!(gm.matches("/work/api/src", ""), None);
+        let gm = GlobMatcher::new("/work/**").unwrap();
+        assert_eq!(gm.matches("/work/api/src", ""), Some(1.0));
+    }
+
+    #[test]
+    fn test_explain() {
+        let sm = SubstringMatcher::default();
+        let ci_sm = CaseInsensitiveMatcher::new(&sm);
+        let pc_ci_sm = PathComponentMatcher::new(&ci_sm);
+
+        assert_eq!(
+            pc_ci_sm.name(),
+            "path component(case-insensitive(substring))"
+        );
+        assert_eq!(pc_ci_sm.explain("/foo/bar", "BAR"), Some((1.0, 0.7 * 0.9)));
+        assert_eq!(
+            pc_ci_sm.explain("/foo/bar", "BAR").map(|(_, v)| v),
+            pc_ci_sm.matches("/foo/bar", "BAR")
+        );
+        assert_eq!(pc_ci_sm.explain("/foo/bar", "baz"), None);
+    }
+
     #[test]
     fn test_substring_matcher() {
-        let sm = SubstringMatcher {};
+        let sm = SubstringMatcher::default();
 
         assert_match_in_order(vec![
             sm.matches("foo", "f"),
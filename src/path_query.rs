@@ -0,0 +1,160 @@
+// path_query recognizes queries which are paths rather than something to match, such as '~/src',
+// '$GOPATH/src' or '../sibling', so that they can be jumped to directly, as 'cd' would.
+
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+// is_path_like returns whether a query should be treated as a path. Queries starting with '/',
+// '~', '.', or an environment variable are paths; anything else is matched against the database,
+// even if it happens to also be a relative path.
+pub fn is_path_like(query: &str) -> bool {
+    let first_component = query.split('/').next().unwrap_or_default();
+    query.starts_with('/')
+        || matches!(first_component, "~" | "." | "..")
+        || query
+            .strip_prefix('$')
+            .and_then(|var| var.chars().next())
+            .is_some_and(|c| c == '{' || c == '_' || c.is_ascii_alphabetic())
+}
+
+// resolve returns the path a path-like query refers to, relative to 'cwd'. A leading '~' is
+// expanded to the home directory, '$VAR' and '${VAR}' to the value of the environment variable,
+// and '.' and '..' are resolved the same way a shell's 'cd' would, without following symlinks.
+// None is returned if the query isn't path-like, or refers to an unset variable.
+pub fn resolve(query: &str, cwd: &Path) -> Option<PathBuf> {
+    if !is_path_like(query) {
+        return None;
+    }
+    let expanded = expand_vars(&expand_home(query)?)?;
+    Some(normalize(&cwd.join(expanded)))
+}
+
+// logical_cwd returns the current directory as the shell sees it, i.e. '$PWD', which may differ
+// from the real current directory if it was reached through a symlink.
+pub fn logical_cwd() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    let pwd = env::var_os("PWD")
+        .map(PathBuf::from)
+        .filter(|pwd| pwd.is_absolute() && pwd.canonicalize().ok().as_ref() == Some(&cwd));
+    Some(pwd.unwrap_or(cwd))
+}
+
+fn expand_home(query: &str) -> Option<String> {
+    match query.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = directories::UserDirs::new()?.home_dir().to_path_buf();
+            Some(format!("{}{}", home.to_str()?, rest))
+        }
+        _ => Some(query.to_string()),
+    }
+}
+
+fn expand_vars(query: &str) -> Option<String> {
+    let mut res = String::new();
+    let mut rest = query;
+    while let Some(ndx) = rest.find('$') {
+        res.push_str(&rest[..ndx]);
+        rest = &rest[ndx + 1..];
+        let (name, remainder) = match rest.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}')?;
+                (&braced[..end], &braced[end + 1..])
+            }
+            None => {
+                let end = rest
+                    .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        if name.is_empty() {
+            // a lone '$' is just a '$'
+            res.push('$');
+        } else {
+            res.push_str(&env::var(name).ok()?);
+        }
+        rest = remainder;
+    }
+    res.push_str(rest);
+    Some(res)
+}
+
+// normalize removes '.' and '..' components from an absolute path without consulting the
+// filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recognizes_path_like_queries() {
+        for query in &[
+            "/tmp",
+            "~",
+            "~/src",
+            ".",
+            "..",
+            "./a",
+            "../a",
+            "$HOME",
+            "${HOME}/src",
+            "$_x",
+        ] {
+            assert!(is_path_like(query), "{} should be path-like", query);
+        }
+        for query in &[
+            "src", "foo$", "^foo", "foo/", "~foo", "...", ".config", "$", "$1",
+        ] {
+            assert!(!is_path_like(query), "{} should not be path-like", query);
+        }
+    }
+
+    #[test]
+    fn resolves_paths() {
+        let cwd = Path::new("/home/user/work/project");
+        let resolve = |q| resolve(q, cwd).map(|p| p.to_string_lossy().into_owned());
+
+        assert_eq!(resolve("src"), None);
+        assert_eq!(resolve("/etc/../tmp"), Some("/tmp".to_string()));
+        assert_eq!(resolve("."), Some("/home/user/work/project".to_string()));
+        assert_eq!(
+            resolve("../sibling"),
+            Some("/home/user/work/sibling".to_string())
+        );
+        assert_eq!(
+            resolve("./a/./b/.."),
+            Some("/home/user/work/project/a".to_string())
+        );
+        assert_eq!(resolve("/.."), Some("/".to_string()));
+
+        env::set_var("PAZI_PATH_QUERY_TEST", "/opt/go");
+        assert_eq!(
+            resolve("$PAZI_PATH_QUERY_TEST/src"),
+            Some("/opt/go/src".to_string())
+        );
+        assert_eq!(
+            resolve("${PAZI_PATH_QUERY_TEST}x/../y"),
+            Some("/opt/y".to_string())
+        );
+        assert_eq!(resolve("$PAZI_PATH_QUERY_TEST_UNSET/src"), None);
+
+        let home = directories::UserDirs::new()
+            .unwrap()
+            .home_dir()
+            .to_path_buf();
+        assert_eq!(resolve("~/src").map(PathBuf::from), Some(home.join("src")));
+    }
+}
@@ -11,6 +11,14 @@ macro_rules! PAZI_EXTENDED_EXIT_CODES_ENV {
     };
 }
 
+// PAZI_INIT_ENV is the environment variable the shell init code sets to '<shell>:<pid>', so
+// 'pazi doctor' can tell whether the shell it's run from was initialized
+macro_rules! PAZI_INIT_ENV {
+    () => {
+        "__PAZI_INIT"
+    };
+}
+
 // Arbitrarily chosen exit codes
 macro_rules! EXIT_CODE {
     (SUCCESS) => {
@@ -0,0 +1,81 @@
+// prune describes which entries 'pazi prune' removes from the database, and why.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::view::format_age;
+
+// Criteria selects what's pruned beyond paths which no longer exist, which always are.
+#[derive(Debug, Default)]
+pub struct Criteria {
+    // paths matching an exclusion
+    pub excluded: bool,
+    // paths last visited longer ago than this; paths whose last visit isn't known are kept
+    pub older_than: Option<Duration>,
+    // paths whose weight, in visits, is below this
+    pub below_weight: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Reason {
+    Missing,
+    // the path exists, but isn't the kind of path the database tracks, e.g. a directory which was
+    // replaced by a file
+    WrongKind(String),
+    Excluded,
+    // seconds since the path was last visited
+    NotVisitedFor(u64),
+    LowWeight(f64),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Missing => write!(f, "missing"),
+            Reason::WrongKind(kind) => write!(f, "not a {}", kind),
+            Reason::Excluded => write!(f, "excluded"),
+            Reason::NotVisitedFor(secs) => write!(f, "last visited {}", format_age(*secs)),
+            Reason::LowWeight(weight) => write!(f, "score {:.2}", weight),
+        }
+    }
+}
+
+// parse_duration parses durations such as '90d', '12h' or '2w'.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid duration '{}': expected a number followed by s, m, h, d or w, e.g. 90d",
+            s
+        )
+    };
+    let unit_start = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (n, unit) = s.split_at(unit_start);
+    let n: u64 = n.parse().map_err(|_| invalid())?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    n.checked_mul(secs)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90d"), Ok(Duration::from_secs(90 * 86400)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_duration("2w"), Ok(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::from_secs(0)));
+        for invalid in &["", "90", "d", "-1d", "1.5d", "3y", "1 d"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
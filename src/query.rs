@@ -0,0 +1,220 @@
+// query parses the lightweight operators a query may contain. Each whitespace separated term of
+// a query may be decorated as follows:
+//
+// ^foo  foo must be at the start of a path component
+// foo$  foo must be at the end of the final path component
+// foo/  foo must be in the final path component
+// !foo  paths containing foo are excluded; '-foo' is equivalent
+//
+// What's left of each term once its operators are removed is what gets matched against paths.
+
+use std::path::MAIN_SEPARATOR;
+
+use super::matcher::Text;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Query {
+    terms: Vec<Term>,
+    exclusions: Vec<String>,
+    ignore_case: bool,
+}
+
+#[derive(Debug, PartialEq)]
+struct Term {
+    text: String,
+    folded: String,
+    start: bool,
+    end: bool,
+    final_component: bool,
+}
+
+impl Term {
+    // is_anchored returns whether the term restricts where in a path it may match
+    fn is_anchored(&self) -> bool {
+        self.start || self.end || self.final_component
+    }
+}
+
+impl Query {
+    // parse parses a query. With smart_case, a query containing an uppercase letter is matched
+    // case-sensitively; otherwise case is ignored.
+    pub fn parse(query: &str, smart_case: bool) -> Self {
+        let mut res = Query {
+            ignore_case: !(smart_case && query.chars().any(char::is_uppercase)),
+            ..Query::default()
+        };
+        for term in query.split_whitespace() {
+            if let Some(excluded) = term.strip_prefix('!').or_else(|| term.strip_prefix('-')) {
+                if !excluded.is_empty() {
+                    res.exclusions.push(if res.ignore_case {
+                        caseless::default_case_fold_str(excluded)
+                    } else {
+                        excluded.to_string()
+                    });
+                }
+                continue;
+            }
+            let (start, term) = match term.strip_prefix('^') {
+                Some(t) => (true, t),
+                None => (false, term),
+            };
+            let (end, term) = match term.strip_suffix('$') {
+                Some(t) => (true, t),
+                None => (false, term),
+            };
+            let (final_component, term) = match term.strip_suffix(MAIN_SEPARATOR) {
+                // a lone separator is a path, not an operator
+                Some(t) if !t.is_empty() => (true, t),
+                _ => (false, term),
+            };
+            if term.is_empty() {
+                continue;
+            }
+            res.terms.push(Term {
+                text: term.to_string(),
+                folded: caseless::default_case_fold_str(term),
+                start,
+                end,
+                final_component,
+            });
+        }
+        res
+    }
+
+    // text is the query to match paths against, with all operators removed
+    pub fn text(&self) -> String {
+        self.terms
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // normalized is the query's text with its operators and exclusions removed, and its case
+    // folded if case is ignored, such that queries which match the same way compare equal.
+    pub fn normalized(&self) -> String {
+        self.terms
+            .iter()
+            .map(|t| {
+                if self.ignore_case {
+                    t.folded.as_str()
+                } else {
+                    t.text.as_str()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    // is_contained_in returns whether every term of the query appears in a path as it is, and the
+    // path satisfies the query's operators. Unlike the matchers, nothing is matched fuzzily. A
+    // query with no terms is contained in nothing.
+    pub fn is_contained_in(&self, path: &Text) -> bool {
+        let folded = path.folded();
+        let text = if self.ignore_case { &folded } else { path.text };
+        !self.terms.is_empty()
+            && self.terms.iter().all(|t| {
+                text.contains(if self.ignore_case {
+                    t.folded.as_str()
+                } else {
+                    t.text.as_str()
+                })
+            })
+            && self.permits(path)
+    }
+
+    // permits returns whether a path satisfies the query's anchors and exclusions.
+    pub fn permits(&self, path: &Text) -> bool {
+        if self.exclusions.is_empty() && self.terms.iter().all(|t| !t.is_anchored()) {
+            return true;
+        }
+        let folded = path.folded();
+        let path = if self.ignore_case { &folded } else { path.text };
+        if self.exclusions.iter().any(|e| path.contains(e.as_str())) {
+            return false;
+        }
+
+        let components: Vec<&str> = path
+            .split(MAIN_SEPARATOR)
+            .filter(|c| !c.is_empty())
+            .collect();
+        // terms without operators are left entirely to the matchers
+        let mut anchored = self.terms.iter().filter(|t| t.is_anchored());
+        anchored.all(|term| {
+            let text = if self.ignore_case {
+                &term.folded
+            } else {
+                &term.text
+            };
+            let candidates = if term.end || term.final_component {
+                components.last().map_or(&[][..], std::slice::from_ref)
+            } else {
+                &components[..]
+            };
+            candidates.iter().any(|c| {
+                (!term.start || c.starts_with(text.as_str()))
+                    && (!term.end || c.ends_with(text.as_str()))
+                    && c.contains(text.as_str())
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::IndexedText;
+
+    fn query_permits(query: &Query, path: &str) -> bool {
+        query.permits(&IndexedText::new(path).text())
+    }
+
+    #[test]
+    fn parses_operators() {
+        let q = Query::parse("^work api$ src/ !old -tmp", true);
+        assert_eq!(q.text(), "work api src");
+        assert_eq!(q.exclusions, vec!["old", "tmp"]);
+        assert!(q.terms[0].start && !q.terms[0].end);
+        assert!(q.terms[1].end && !q.terms[1].start);
+        assert!(q.terms[2].final_component);
+
+        let q = Query::parse("dev/tool / ! -", true);
+        assert_eq!(q.text(), "dev/tool /");
+        assert!(q.exclusions.is_empty());
+    }
+
+    #[test]
+    fn normalizes_queries() {
+        let normalized = |q: &str| Query::parse(q, true).normalized();
+        assert_eq!(normalized("^foo$ bar/ !baz"), "foo bar");
+        assert_eq!(normalized("  foo   bar "), "foo bar");
+        assert_eq!(Query::parse("Foo", false).normalized(), "foo");
+        assert_eq!(normalized("Foo"), "Foo");
+    }
+
+    #[test]
+    fn permits_paths() {
+        let permits = |q: &str, path: &str| query_permits(&Query::parse(q, false), path);
+
+        assert!(permits("fo", "/x/bar"));
+        assert!(permits("^foo", "/x/foobar"));
+        assert!(!permits("^foo", "/x/barfoo"));
+        assert!(permits("foo$", "/x/barfoo"));
+        assert!(!permits("foo$", "/foo/bar"));
+        assert!(!permits("foo$", "/x/foobar"));
+        assert!(permits("foo/", "/x/afoob"));
+        assert!(!permits("foo/", "/foo/bar"));
+        assert!(permits("^foo$", "/x/foo"));
+        assert!(!permits("^foo$", "/x/foox"));
+        assert!(permits("foo !bar", "/x/foo"));
+        assert!(!permits("foo !bar", "/bar/foo"));
+        assert!(!permits("foo -BAR", "/bar/foo"));
+        // with smart case, uppercase makes the whole query case-sensitive
+        assert!(query_permits(&Query::parse("foo -BAR", true), "/bar/foo"));
+        assert!(!query_permits(&Query::parse("^Foo", true), "/bar/foo"));
+    }
+}
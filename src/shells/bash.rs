@@ -7,10 +7,14 @@ impl Shell for Bash {
         // PROMPT_COMMAND modification inspired by https://github.com/clvv/fasd/blob/90b531a5daaa545c74c7d98974b54cbdb92659fc/fasd#L132-L136
         concat!(
             r#"
+export "#,
+            PAZI_INIT_ENV!(),
+            r#"="bash:$$"
+
 __pazi_add_dir() {
     # TODO: should pazi keep track of this itself in its datadir?
     if [[ "${__PAZI_LAST_PWD:-}" != "${PWD}" ]]; then
-        { pazi visit "${PWD}" & }; disown 2>/dev/null || true
+        { pazi visit ${__PAZI_LAST_PWD:+--leaving "${__PAZI_LAST_PWD}"} "${PWD}" & }; disown 2>/dev/null || true
     fi
     __PAZI_LAST_PWD="${PWD}"
 }
@@ -46,6 +50,14 @@ pazi_cd() {
     esac
 }
 alias z='pazi_cd'
+
+pazi_open() {
+    local res
+    res="$(pazi file "$@")" || return 1
+    { pazi visit --file "${res}" & }; disown 2>/dev/null || true
+    ${EDITOR:-vi} "${res}"
+}
+alias zo='pazi_open'
 "#
         )
     }
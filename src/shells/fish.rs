@@ -6,6 +6,10 @@ impl Shell for Fish {
     fn pazi_init(&self) -> &'static str {
         concat!(
             r#"
+set -gx "#,
+            PAZI_INIT_ENV!(),
+            r#" "fish:$fish_pid"
+
 function pazi_cd
     if [ (count $argv) -eq 0 ]
         pazi view
@@ -36,10 +40,27 @@ end
 
 function __pazi_preexec --on-variable PWD
     status --is-command-substitution; and return
-    pazi visit (pwd)
+    if set -q __pazi_last_pwd
+        pazi visit --leaving $__pazi_last_pwd (pwd)
+    else
+        pazi visit (pwd)
+    end
+    set -g __pazi_last_pwd (pwd)
 end
 
 alias z 'pazi_cd'
+
+function pazi_open
+    set -l res (pazi file $argv); or return 1
+    pazi visit --file $res
+    if set -q EDITOR
+        eval $EDITOR (string escape -- $res)
+    else
+        vi $res
+    end
+end
+
+alias zo 'pazi_open'
 "#
         )
     }
@@ -6,8 +6,12 @@ impl Shell for Zsh {
     fn pazi_init(&self) -> &'static str {
         concat!(
             r#"
+export "#,
+            PAZI_INIT_ENV!(),
+            r#"="zsh:$$"
+
 __pazi_add_dir() {
-    pazi visit "${PWD}" &!
+    pazi visit --leaving "${OLDPWD}" "${PWD}" &!
 }
 
 autoload -Uz add-zsh-hook
@@ -38,9 +42,20 @@ pazi_cd() {
 }
 alias z='pazi_cd'
 
+pazi_open() {
+    local res
+    res="$(pazi file "$@")" || return 1
+    pazi visit --file "${res}" &!
+    ${=EDITOR:-vi} "${res}"
+}
+alias zo='pazi_open'
+
 _pazi_cd() {
   CURRENTWORD="${LBUFFER/* /}${RBUFFER/ */}"
-  local suggestions=(${(f)"$(pazi complete zsh -- $CURRENTWORD)"})
+  local mode=()
+  (( ${words[(I)(-r|--regex)]} )) && mode=(--regex)
+  (( ${words[(I)(-g|--glob)]} )) && mode=(--glob)
+  local suggestions=(${(f)"$(pazi complete zsh $mode -- $CURRENTWORD)"})
   _describe -V -t pazi-dirs 'pazi' suggestions
 }
 
@@ -0,0 +1,321 @@
+// view renders the entries of the frecency database shown by 'pazi view'.
+
+use std::cmp::Reverse;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+use unicode_width::UnicodeWidthStr;
+
+pub const FORMATS: &[&str] = &["table", "tsv", "json"];
+pub const SORTS: &[&str] = &["score", "recent", "alpha", "visits"];
+pub const FIELDS: &[&str] = &["score", "path", "visits", "last_visit", "pin"];
+// DEFAULT_FIELDS are the columns 'pazi view' has always printed as tsv, which scripts rely on
+pub const DEFAULT_FIELDS: &[Field] = &[Field::Score, Field::Path];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // aligned columns with a header, for people
+    Table,
+    // tab separated columns, by default the score and path, for scripts
+    Tsv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "tsv" => Ok(Format::Tsv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format '{}'", s)),
+        }
+    }
+}
+
+// Field is a column of tsv output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Score,
+    Path,
+    Visits,
+    // in seconds since the unix epoch
+    LastVisit,
+    Pin,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "score" => Ok(Field::Score),
+            "path" => Ok(Field::Path),
+            "visits" => Ok(Field::Visits),
+            "last_visit" => Ok(Field::LastVisit),
+            "pin" => Ok(Field::Pin),
+            _ => Err(format!("unknown field '{}'", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sort {
+    Score,
+    Recent,
+    Alpha,
+    Visits,
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "score" => Ok(Sort::Score),
+            "recent" => Ok(Sort::Recent),
+            "alpha" => Ok(Sort::Alpha),
+            "visits" => Ok(Sort::Visits),
+            _ => Err(format!("unknown sort order '{}'", s)),
+        }
+    }
+}
+
+// Entry is a single path to view. Visits and the last visit are only known for paths visited
+// since they started being recorded.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Entry {
+    pub path: String,
+    pub score: f64,
+    pub visits: Option<u64>,
+    // in seconds since the unix epoch
+    pub last_visit: Option<u64>,
+    pub pinned: bool,
+    pub pin_name: Option<String>,
+}
+
+impl Entry {
+    fn pin_note(&self) -> String {
+        match (self.pinned, &self.pin_name) {
+            (_, Some(name)) => format!("(pinned as {})", name),
+            (true, None) => "(pinned)".to_string(),
+            (false, None) => String::new(),
+        }
+    }
+}
+
+// sort orders entries, which are expected to already be ordered by score. Ties keep that order,
+// and entries whose visits aren't known sort after those whose are.
+pub fn sort(entries: &mut [Entry], sort: Sort) {
+    match sort {
+        Sort::Score => {}
+        Sort::Recent => entries.sort_by_key(|e| Reverse(e.last_visit)),
+        Sort::Alpha => entries.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path)),
+        Sort::Visits => entries.sort_by_key(|e| Reverse(e.visits)),
+    }
+}
+
+// write writes entries in the given format. Tsv is written with the given fields; the other
+// formats always include everything. Tables show when each entry was last visited relative to
+// 'now', in seconds since the unix epoch.
+pub fn write(
+    out: &mut dyn Write,
+    entries: &[Entry],
+    format: Format,
+    fields: &[Field],
+    now: u64,
+) -> io::Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer(&mut *out, entries)?;
+            writeln!(out)
+        }
+        Format::Tsv => {
+            let unknown = String::new;
+            for e in entries {
+                let mut values: Vec<String> = fields
+                    .iter()
+                    .map(|field| match field {
+                        Field::Score => format_score(e.score),
+                        Field::Path => e.path.clone(),
+                        Field::Visits => e.visits.map_or_else(unknown, |v| v.to_string()),
+                        Field::LastVisit => e.last_visit.map_or_else(unknown, |t| t.to_string()),
+                        Field::Pin => e.pin_note(),
+                    })
+                    .collect();
+                // empty fields are only kept where they hold later fields in place
+                while values.last().is_some_and(String::is_empty) {
+                    values.pop();
+                }
+                writeln!(out, "{}", values.join("\t"))?;
+            }
+            Ok(())
+        }
+        Format::Table => {
+            if entries.is_empty() {
+                return Ok(());
+            }
+            let unknown = || "-".to_string();
+            let mut rows = vec![["SCORE", "VISITS", "LAST VISIT", "PATH", ""].map(String::from)];
+            rows.extend(entries.iter().map(|e| {
+                [
+                    format_score(e.score),
+                    e.visits.map_or_else(unknown, |v| v.to_string()),
+                    e.last_visit
+                        .map_or_else(unknown, |t| format_age(now.saturating_sub(t))),
+                    e.path.clone(),
+                    e.pin_note(),
+                ]
+            }));
+            write_table(out, &rows, &[true, true, false, false, false])
+        }
+    }
+}
+
+fn format_score(score: f64) -> String {
+    // precision for floats only handles the floating part, which leads to unaligned
+    // output, e.g., for a precision value of '3', you might get:
+    // 1.000
+    // 100.000
+    //
+    // By converting it to a string first, and then truncating it, we can get nice prettily
+    // aligned strings.
+    // Note: the string's precision should be at least as long as the printed precision so
+    // there are enough characters.
+    format!("{:.5}", format!("{:.5}", score))
+}
+
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+// write_table writes rows as columns separated by two spaces. Cells are padded by their display
+// width, so that paths with wide or combining characters still line up.
+fn write_table<const N: usize>(
+    out: &mut dyn Write,
+    rows: &[[String; N]],
+    right_align: &[bool; N],
+) -> io::Result<()> {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    for row in rows {
+        let mut line = String::new();
+        for ((cell, width), &right) in row.iter().zip(widths).zip(right_align) {
+            let padding = " ".repeat(width - cell.width());
+            if right {
+                line.push_str(&padding);
+                line.push_str(cell);
+            } else {
+                line.push_str(cell);
+                line.push_str(&padding);
+            }
+            line.push_str("  ");
+        }
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(path: &str, visits: Option<u64>, last_visit: Option<u64>) -> Entry {
+        Entry {
+            path: path.to_string(),
+            score: 50.0,
+            visits,
+            last_visit,
+            pinned: false,
+            pin_name: None,
+        }
+    }
+
+    fn render(entries: &[Entry], format: Format) -> String {
+        render_fields(entries, format, DEFAULT_FIELDS)
+    }
+
+    fn render_fields(entries: &[Entry], format: Format, fields: &[Field]) -> String {
+        let mut out = Vec::new();
+        write(&mut out, entries, format, fields, 100_000).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn sorts_entries() {
+        let mut entries = vec![
+            entry("/b", Some(1), Some(20)),
+            entry("/c", None, None),
+            entry("/a", Some(3), Some(10)),
+            entry("/d", Some(1), Some(30)),
+        ];
+        let paths = |entries: &[Entry]| entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+        sort(&mut entries, Sort::Visits);
+        assert_eq!(paths(&entries), vec!["/a", "/b", "/d", "/c"]);
+        sort(&mut entries, Sort::Recent);
+        assert_eq!(paths(&entries), vec!["/d", "/b", "/a", "/c"]);
+        sort(&mut entries, Sort::Alpha);
+        assert_eq!(paths(&entries), vec!["/a", "/b", "/c", "/d"]);
+    }
+
+    #[test]
+    fn aligns_tables_by_display_width() {
+        let mut pinned = entry("/日本語", Some(12), Some(100_000 - 7200));
+        pinned.pinned = true;
+        pinned.pin_name = Some("jp".to_string());
+        let entries = vec![
+            pinned,
+            entry("/cafe\u{301}/x", Some(3), Some(100_000 - 30)),
+            entry("/old", None, None),
+        ];
+        assert_eq!(
+            render(&entries, Format::Table),
+            "\
+SCORE  VISITS  LAST VISIT  PATH
+50.00      12  2h ago      /日本語  (pinned as jp)
+50.00       3  just now    /cafe\u{301}/x
+50.00       -  -           /old
+"
+        );
+        assert_eq!(render(&[], Format::Table), "");
+    }
+
+    #[test]
+    fn writes_tsv_and_json() {
+        let entries = vec![entry("/a", Some(2), Some(10)), entry("/b", None, None)];
+        assert_eq!(render(&entries, Format::Tsv), "50.00\t/a\n50.00\t/b\n");
+        let all = [
+            Field::Score,
+            Field::Path,
+            Field::Visits,
+            Field::LastVisit,
+            Field::Pin,
+        ];
+        assert_eq!(
+            render_fields(&entries, Format::Tsv, &all),
+            "50.00\t/a\t2\t10\n50.00\t/b\n"
+        );
+        // empty fields before others are kept so columns line up
+        assert_eq!(
+            render_fields(&entries, Format::Tsv, &[Field::Visits, Field::Path]),
+            "2\t/a\n\t/b\n"
+        );
+        assert_eq!(
+            render(&entries[..1], Format::Json),
+            r#"[{"path":"/a","score":50.0,"visits":2,"last_visit":10,"pinned":false,"pin_name":null}]"#
+                .to_string()
+                + "\n"
+        );
+    }
+}
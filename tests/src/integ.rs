@@ -300,3 +300,266 @@ fn it_pipes_shell(shell: &Shell) {
     assert_eq!("0", h.run_cmd_with_status("z --pipe 'tail -n 1'"));
     assert_eq!(last_dir, h.run_cmd("pwd"));
 }
+
+#[test]
+fn it_finds_frecent_files() {
+    for shell in &Pazi.supported_shells() {
+        it_finds_frecent_files_shell(shell);
+    }
+}
+
+fn it_finds_frecent_files_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+
+    let src_dir = root.join("src");
+    h.create_dir(&src_dir.to_string_lossy());
+    let main_rs = src_dir.join("main.rs");
+    std::fs::write(&main_rs, "fn main() {}\n").unwrap();
+    let main_rs = main_rs.to_string_lossy();
+
+    assert_eq!("0", h.run_cmd_with_status(&format!("pazi visit --file '{}'", main_rs)));
+    assert_eq!(h.run_cmd("pazi file main"), main_rs);
+    // files and directories are tracked separately
+    assert_eq!(h.run_cmd("pazi view"), "");
+}
+
+#[test]
+fn it_jumps_with_multiple_terms() {
+    for shell in &Pazi.supported_shells() {
+        it_jumps_with_multiple_terms_shell(shell);
+    }
+}
+
+fn it_jumps_with_multiple_terms_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let work_api = root.join("work/api").to_string_lossy().to_string();
+    let api_work = root.join("api/work").to_string_lossy().to_string();
+
+    h.create_dir(&work_api);
+    h.create_dir(&api_work);
+    h.visit_dir(&work_api);
+    h.visit_dir(&api_work);
+    h.visit_dir(&root.to_string_lossy());
+
+    assert_eq!(h.jump("work api"), work_api);
+    assert_eq!(h.jump("api work"), api_work);
+}
+
+#[test]
+fn it_jumps_with_patterns() {
+    for shell in &Pazi.supported_shells() {
+        it_jumps_with_patterns_shell(shell);
+    }
+}
+
+fn it_jumps_with_patterns_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let svc = root.join("svc-12").to_string_lossy().to_string();
+    let svc_src = root.join("svc-12/src").to_string_lossy().to_string();
+    let prod = root.join("deploy/us-prod").to_string_lossy().to_string();
+
+    h.create_dir(&svc_src);
+    h.create_dir(&prod);
+    h.visit_dir(&svc);
+    h.visit_dir(&svc_src);
+    h.visit_dir(&svc_src);
+    h.visit_dir(&prod);
+    h.visit_dir(&root.to_string_lossy());
+
+    let regex_res = h.run_cmd("pazi view -r 'svc-[0-9]+$'");
+    assert!(regex_res.contains(&svc));
+    assert!(!regex_res.contains(&svc_src));
+    assert!(!regex_res.contains(&prod));
+    let glob_res = h.run_cmd("pazi view -g '**/deploy/*prod*'");
+    assert!(glob_res.contains(&prod));
+    assert!(!glob_res.contains(&svc));
+}
+
+#[test]
+fn it_jumps_to_path_queries() {
+    for shell in &Pazi.supported_shells() {
+        it_jumps_to_path_queries_shell(shell);
+    }
+}
+
+fn it_jumps_to_path_queries_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    // the harness's shells use 'home/pazi' under the root as their home directory
+    let home_src = root.join("home/pazi/src").to_string_lossy().to_string();
+    let other_src = root.join("work/src").to_string_lossy().to_string();
+    let sibling = root.join("work/sibling").to_string_lossy().to_string();
+
+    h.create_dir(&home_src);
+    h.create_dir(&other_src);
+    h.create_dir(&sibling);
+    for _ in 0..4 {
+        h.visit_dir(&other_src);
+        h.visit_dir(&root.to_string_lossy());
+    }
+
+    // path-like queries go straight to the directory, rather than the best match for 'src'
+    assert_eq!(h.jump("~/src"), home_src);
+    assert_eq!(h.jump("$HOME/src"), home_src);
+    h.visit_dir(&other_src);
+    assert_eq!(h.jump("../sibling"), sibling);
+    // and are recorded in the database
+    assert!(h.run_cmd("pazi view").contains(&sibling));
+    // with the visit counted once, by the shell's hook, rather than also by the jump
+    let unvisited = root.join("work/unvisited").to_string_lossy().to_string();
+    h.create_dir(&unvisited);
+    assert_eq!(h.jump(&unvisited), unvisited);
+    let view = h.run_cmd("pazi view --format json");
+    let entry = view
+        .split('{')
+        .find(|e| e.contains(&format!("\"{}\"", unvisited)))
+        .unwrap();
+    assert!(entry.contains("\"visits\":1,"), "{}", entry);
+    // other queries are matched as usual
+    assert_eq!(h.jump("src"), other_src);
+}
+
+#[test]
+fn it_adds_and_removes_directories() {
+    for shell in &Pazi.supported_shells() {
+        it_adds_and_removes_directories_shell(shell);
+    }
+}
+
+fn it_adds_and_removes_directories_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let one = root.join("proj/one").to_string_lossy().to_string();
+    let two = root.join("proj/two").to_string_lossy().to_string();
+    let other = root.join("other").to_string_lossy().to_string();
+
+    h.create_dir(&one);
+    h.create_dir(&two);
+    h.create_dir(&other);
+    let add = format!("pazi add '{}' --score 5 '{}/proj/*'", other, root.display());
+    assert_eq!("0", h.run_cmd_with_status(&add));
+    assert_eq!(h.jump("proj"), one);
+
+    // only directories which contain the query are removed, never fuzzy matches
+    let apple_pie = root.join("apple-pie").to_string_lossy().to_string();
+    h.create_dir(&apple_pie);
+    h.visit_dir(&apple_pie);
+    assert_eq!("1", h.run_cmd_with_status("pazi remove --match aple --yes"));
+    assert!(h.run_cmd("pazi view").contains(&apple_pie));
+
+    // removing several directories at once has to be confirmed
+    assert_eq!("1", h.run_cmd_with_status("pazi remove --match proj"));
+    assert!(h.run_cmd("pazi view").contains(&two));
+    assert_eq!("0", h.run_cmd_with_status("pazi remove --match proj --yes"));
+    assert_eq!(
+        "0",
+        h.run_cmd_with_status(&format!("pazi remove '{}' --dry-run", other))
+    );
+    let view = h.run_cmd("pazi view");
+    assert!(view.contains(&other));
+    assert!(!view.contains(&one));
+    assert!(!view.contains(&two));
+}
+
+#[test]
+fn it_queries_without_changing_the_database() {
+    for shell in &Pazi.supported_shells() {
+        it_queries_without_changing_the_database_shell(shell);
+    }
+}
+
+fn it_queries_without_changing_the_database_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let one = root.join("proj/one").to_string_lossy().to_string();
+    let two = root.join("proj/two").to_string_lossy().to_string();
+
+    h.create_dir(&one);
+    h.create_dir(&two);
+    // the shell has to leave 'one' for returning to it to count as another visit
+    h.visit_dir(&one);
+    h.visit_dir(&two);
+    h.visit_dir(&one);
+    h.visit_dir(&root.to_string_lossy());
+    std::fs::remove_dir(&two).unwrap();
+
+    assert_eq!(h.run_cmd("pazi query proj"), format!("{}\n{}", one, two));
+    assert_eq!(h.run_cmd("pazi query --exists-only proj"), one);
+    let json = h.run_cmd("pazi query --limit 1 --json proj");
+    assert!(json.starts_with(&format!(r#"[{{"path":"{}","score":"#, one)));
+    assert_eq!("1", h.run_cmd_with_status("pazi query nothing-matches"));
+    // the missing directory is still there
+    assert!(h.run_cmd("pazi query").contains(&two));
+}
+
+#[test]
+fn it_prunes_missing_directories() {
+    for shell in &Pazi.supported_shells() {
+        it_prunes_missing_directories_shell(shell);
+    }
+}
+
+fn it_prunes_missing_directories_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let kept = root.join("kept").to_string_lossy().to_string();
+    let gone = root.join("gone").to_string_lossy().to_string();
+    let file = root.join("file").to_string_lossy().to_string();
+
+    for dir in &[&kept, &gone, &file] {
+        h.create_dir(dir);
+        h.visit_dir(dir);
+    }
+    h.visit_dir(&root.to_string_lossy());
+    std::fs::remove_dir(&gone).unwrap();
+    std::fs::remove_dir(&file).unwrap();
+    std::fs::write(&file, "").unwrap();
+
+    let dry_run = h.run_cmd("pazi prune --dry-run");
+    assert_eq!(
+        dry_run,
+        format!(
+            "would remove {} (not a directory)\nwould remove {} (missing)",
+            file, gone
+        )
+    );
+    // nothing was removed, so the same paths are still there to prune
+    assert_eq!(h.run_cmd("pazi prune --dry-run"), dry_run);
+    assert!(h.run_cmd("pazi query").contains(&gone));
+    assert_eq!("0", h.run_cmd_with_status("pazi prune"));
+    let view = h.run_cmd("pazi view");
+    assert!(view.contains(&kept));
+    assert!(!view.contains(&gone));
+    assert!(!view.contains(&file));
+}
+
+#[test]
+fn it_diagnoses_its_setup() {
+    for shell in &Pazi.supported_shells() {
+        it_diagnoses_its_setup_shell(shell);
+    }
+}
+
+fn it_diagnoses_its_setup_shell(shell: &Shell) {
+    let tmpdir = TempDir::new("pazi_integ").unwrap();
+    let root = tmpdir.path().canonicalize().unwrap();
+    let mut h = HarnessBuilder::new(&root, &Pazi, shell).finish();
+    let dir = root.join("dir").to_string_lossy().to_string();
+
+    h.create_dir(&dir);
+    h.visit_dir(&dir);
+    let res = h.run_cmd("pazi doctor");
+    assert!(res.contains(&format!("[ok] pazi is initialized for {}", shell.name())));
+    assert!(res.contains("[ok] the last visit was recorded just now"));
+    assert!(!res.contains("[FAIL]"));
+}